@@ -0,0 +1,37 @@
+//! Sends a dead-letter file written by `JsonlDeadLetterSink` again.
+//!
+//! cargo run --example resubmit_dead_letters -- dead-letters.jsonl
+
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use timberlogs::{JsonlDeadLetterSink, TimberlogsClient, TimberlogsConfig};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: resubmit_dead_letters <file.jsonl>");
+        return ExitCode::FAILURE;
+    };
+
+    // Entries rejected again go to a separate file rather than back into
+    // the one being read.
+    let rejected = format!("{path}.rejected");
+    let sink = JsonlDeadLetterSink::new(&rejected).expect("failed to open rejected file");
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        api_key: std::env::var("TIMBERLOGS_API_KEY").expect("TIMBERLOGS_API_KEY required"),
+        dead_letter: Some(Arc::new(sink)),
+        ..Default::default()
+    });
+
+    match client.resubmit_dead_letters(&path).await {
+        Ok(sent) => {
+            println!("resubmitted {sent} logs from {path}; rejected ones are in {rejected}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("resubmit failed, {path} was left as is: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::TimberlogsClient;
+use crate::error::TimberlogsError;
+use crate::types::{LogEntry, LogLevel, RawFormat};
+
+const DEFAULT_POLL_INTERVAL_MS: u64 = 1000;
+// Read at most this much of a file per poll, so a large backlog is shipped
+// in steps rather than held in memory at once.
+const MAX_READ_BYTES: u64 = 1024 * 1024;
+const MAX_MESSAGE_BYTES: usize = 10_000;
+// How much of the start of a file is remembered, to notice it being
+// truncated and rewritten in place (copytruncate) between polls.
+const HEAD_BYTES: u64 = 256;
+
+const LINE_ID_NAMESPACE: uuid::Uuid = uuid::Uuid::from_u128(0x0c5f3a6e_8f1d_4b7a_9d2e_6a41c7b3e905);
+
+/// A file for [`FileAgent`] to follow.
+#[derive(Debug, Clone)]
+pub struct WatchedFile {
+    pub path: PathBuf,
+    /// How each line is parsed: `Text`, `Jsonl` or `Syslog`.
+    pub format: RawFormat,
+    /// Level for lines that don't carry one. Defaults to `Info`.
+    pub level: LogLevel,
+    pub dataset: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+impl WatchedFile {
+    pub fn new(path: impl Into<PathBuf>, format: RawFormat) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            level: LogLevel::Info,
+            dataset: None,
+            tags: None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FileAgentConfig {
+    pub files: Vec<WatchedFile>,
+    /// Where read offsets are saved once the logs before them are sent.
+    pub checkpoint_path: PathBuf,
+    /// Defaults to 1000.
+    pub poll_interval_ms: Option<u64>,
+    /// For files that exist at startup without a checkpoint: ship what they
+    /// already contain instead of only new lines.
+    pub read_existing: bool,
+}
+
+/// Which file a path pointed at, so a rotated file can be told apart from
+/// the one that replaced it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileId {
+    device: u64,
+    inode: u64,
+}
+
+/// Digest of the first `len` bytes of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Head {
+    len: u64,
+    digest: String,
+}
+
+/// Where reading got to in a file. Also what's saved in the checkpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Position {
+    #[serde(flatten)]
+    id: Option<FileId>,
+    /// Bumped each time the file is rotated or truncated, so lines at the
+    /// same offset of the old and new contents get different ids.
+    #[serde(default)]
+    generation: u64,
+    // Start of the first line not yet queued.
+    offset: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    head: Option<Head>,
+}
+
+impl Position {
+    fn restart(&mut self, id: Option<FileId>) {
+        self.id = id;
+        self.generation += 1;
+        self.offset = 0;
+        self.head = None;
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CheckpointFile {
+    files: HashMap<PathBuf, Position>,
+}
+
+struct FileState {
+    watched: WatchedFile,
+    file: Option<File>,
+    position: Position,
+    // Set until the file has been opened once, so only files that already
+    // existed at startup can be skipped to the end.
+    at_startup: bool,
+}
+
+/// Follows log files and ships their lines through a [`TimberlogsClient`].
+///
+/// Rotation (the path now points at a different file) and truncation (the
+/// file shrank, or its first bytes changed) are detected on each poll. When a
+/// file is rotated, what is left of the old file is read before moving on to
+/// the new one. Offsets are checkpointed only after the lines before them
+/// were flushed, and each line is sent with an id derived from its file,
+/// generation and offset, so a restart neither skips lines nor ships them
+/// twice. Rotation is detected by inode, so on platforms without inodes a
+/// new file at the path is only noticed by its first bytes.
+pub struct FileAgent {
+    client: Arc<TimberlogsClient>,
+    files: Vec<FileState>,
+    checkpoint_path: PathBuf,
+    poll_interval: Duration,
+    // Offsets were queued past the last checkpoint.
+    dirty: bool,
+}
+
+impl FileAgent {
+    /// Loads the checkpoint file if there is one.
+    pub fn new(
+        client: Arc<TimberlogsClient>,
+        config: FileAgentConfig,
+    ) -> Result<Self, TimberlogsError> {
+        if config.files.is_empty() {
+            return Err(TimberlogsError::Validation("no files to follow".into()));
+        }
+        let checkpoints = match fs::read(&config.checkpoint_path) {
+            Ok(bytes) => serde_json::from_slice::<CheckpointFile>(&bytes).map_err(|e| {
+                TimberlogsError::Validation(format!(
+                    "invalid checkpoint file {}: {e}",
+                    config.checkpoint_path.display()
+                ))
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => CheckpointFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut files = Vec::new();
+        for watched in config.files {
+            if !matches!(
+                watched.format,
+                RawFormat::Text | RawFormat::Jsonl | RawFormat::Syslog
+            ) {
+                return Err(TimberlogsError::Validation(format!(
+                    "{}: files can only be followed as text, jsonl or syslog, not {}",
+                    watched.path.display(),
+                    watched.format.as_str()
+                )));
+            }
+            let checkpoint = checkpoints.files.get(&watched.path);
+            files.push(FileState {
+                file: None,
+                position: checkpoint.cloned().unwrap_or_default(),
+                at_startup: checkpoint.is_none() && !config.read_existing,
+                watched,
+            });
+        }
+        Ok(Self {
+            client,
+            files,
+            checkpoint_path: config.checkpoint_path,
+            poll_interval: Duration::from_millis(
+                config.poll_interval_ms.unwrap_or(DEFAULT_POLL_INTERVAL_MS),
+            ),
+            dirty: false,
+        })
+    }
+
+    /// Polls until the future is dropped. Errors go to the client's
+    /// `on_error` and polling carries on.
+    pub async fn run(&mut self) {
+        let executor = self.client.executor();
+        loop {
+            if let Err(e) = self.poll().await {
+                self.client.report_error(&e);
+            }
+            executor.sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Queues new lines from every file, then flushes the client and saves
+    /// the checkpoint. Returns how many lines were queued.
+    ///
+    /// If the flush fails, the lines stay queued in the client and the
+    /// checkpoint is saved by a later poll once they are sent.
+    pub async fn poll(&mut self) -> Result<usize, TimberlogsError> {
+        let mut queued = 0;
+        for index in 0..self.files.len() {
+            match self.read_file(index).await {
+                Ok(count) => queued += count,
+                // Not created yet, or rotated away and not yet replaced.
+                Err(TimberlogsError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                    self.files[index].at_startup = false;
+                }
+                Err(e) => self.client.report_error(&e),
+            }
+        }
+        self.checkpoint().await?;
+        Ok(queued)
+    }
+
+    /// Flushes the client and, if that succeeds, saves the offsets of
+    /// everything queued so far. Call it once more before exiting.
+    pub async fn checkpoint(&mut self) -> Result<(), TimberlogsError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.client.flush().await?;
+        let checkpoints = CheckpointFile {
+            files: self
+                .files
+                .iter()
+                .map(|state| (state.watched.path.clone(), state.position.clone()))
+                .collect(),
+        };
+        let json = serde_json::to_vec(&checkpoints)
+            .map_err(|e| TimberlogsError::Validation(e.to_string()))?;
+        // Written aside and renamed over, so a crash leaves the old or the
+        // new checkpoint and never half of one.
+        let tmp = self.checkpoint_path.with_extension("tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &self.checkpoint_path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    async fn read_file(&mut self, index: usize) -> Result<usize, TimberlogsError> {
+        let mut queued = 0;
+        let state = &mut self.files[index];
+        let mut current = File::open(&state.watched.path)?;
+        let metadata = current.metadata()?;
+        let id = file_id(&metadata);
+        let len = metadata.len();
+        let position = &mut state.position;
+
+        if state.file.is_none() {
+            if state.at_startup {
+                position.id = id;
+                position.offset = len;
+            } else if position.offset > 0
+                && (position.id != id || !same_head(&mut current, position, len)?)
+            {
+                // Replaced or truncated while the agent wasn't running.
+                position.restart(id);
+            } else {
+                position.id = id;
+            }
+            state.at_startup = false;
+            state.file = Some(current);
+            self.dirty = true;
+        } else if id != position.id {
+            // Rotated: finish the old file, including a last unterminated
+            // line, then start the new one from the top.
+            queued += self.queue_lines(index, true).await?;
+            let state = &mut self.files[index];
+            state.position.restart(id);
+            state.file = Some(current);
+            self.dirty = true;
+        } else if !same_head(&mut current, position, len)? {
+            position.restart(id);
+            state.file = Some(current);
+            self.dirty = true;
+        }
+        queued += self.queue_lines(index, false).await?;
+
+        let state = &mut self.files[index];
+        let head_len = state.position.offset.min(HEAD_BYTES);
+        if state.position.head.as_ref().map_or(0, |head| head.len) < head_len {
+            if let Some(file) = state.file.as_mut() {
+                state.position.head = read_head(file, head_len)?;
+            }
+        }
+        Ok(queued)
+    }
+
+    /// Queues complete lines from the file's offset onwards, or every line
+    /// if `to_end`.
+    async fn queue_lines(&mut self, index: usize, to_end: bool) -> Result<usize, TimberlogsError> {
+        let mut queued = 0;
+        loop {
+            let state = &mut self.files[index];
+            let Some(file) = state.file.as_mut() else {
+                return Ok(queued);
+            };
+            let start = state.position.offset;
+            let mut buf = Vec::new();
+            file.seek(SeekFrom::Start(start))?;
+            file.take(MAX_READ_BYTES).read_to_end(&mut buf)?;
+            if buf.is_empty() {
+                return Ok(queued);
+            }
+            let full = buf.len() as u64 == MAX_READ_BYTES;
+            let end = match buf.iter().rposition(|&b| b == b'\n') {
+                _ if to_end => buf.len(),
+                Some(newline) => newline + 1,
+                // A line longer than a whole read is cut into pieces.
+                None if full => buf.len(),
+                None => return Ok(queued),
+            };
+
+            let mut line_start = 0;
+            while line_start < end {
+                let line_end = buf[line_start..end]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(end, |i| line_start + i + 1);
+                let state = &self.files[index];
+                let offset = start + line_start as u64;
+                let text = String::from_utf8_lossy(&buf[line_start..line_end]);
+                let text = text.trim_end_matches(['\n', '\r']);
+                if let Some(entry) = parse_line(&state.watched, &state.position, offset, text) {
+                    match self.client.log(entry).await {
+                        Ok(()) => queued += 1,
+                        Err(e) => self.client.report_error(&e),
+                    }
+                }
+                // Advanced per line, so stopping part way through a read
+                // neither loses nor repeats lines.
+                self.files[index].position.offset = offset + (line_end - line_start) as u64;
+                self.dirty = true;
+                line_start = line_end;
+            }
+            if !full {
+                return Ok(queued);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(FileId {
+        device: metadata.dev(),
+        inode: metadata.ino(),
+    })
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<FileId> {
+    None
+}
+
+/// Whether the file still holds everything before the offset: it hasn't
+/// shrunk below it, and starts with the bytes it started with.
+fn same_head(file: &mut File, position: &Position, len: u64) -> io::Result<bool> {
+    if len < position.offset {
+        return Ok(false);
+    }
+    match &position.head {
+        Some(head) => Ok(read_head(file, head.len)?.as_ref() == Some(head)),
+        None => Ok(true),
+    }
+}
+
+fn read_head(file: &mut File, len: u64) -> io::Result<Option<Head>> {
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Ok(None);
+    }
+    let digest = uuid::Uuid::new_v5(&LINE_ID_NAMESPACE, &bytes).to_string();
+    Ok(Some(Head { len, digest }))
+}
+
+fn parse_line(
+    watched: &WatchedFile,
+    position: &Position,
+    offset: u64,
+    line: &str,
+) -> Option<LogEntry> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    let mut entry = match watched.format {
+        RawFormat::Jsonl => parse_json_line(line, watched.level),
+        RawFormat::Syslog => parse_syslog_line(line),
+        _ => None,
+    }
+    .unwrap_or_else(|| LogEntry {
+        level: watched.level,
+        ..Default::default()
+    });
+    if entry.message.trim().is_empty() {
+        entry.message = line.to_string();
+    }
+    truncate(&mut entry.message, MAX_MESSAGE_BYTES);
+
+    let generation = position.generation;
+    let key = match position.id {
+        Some(id) => format!("{}:{}:{generation}:{offset}", id.device, id.inode),
+        None => format!("{}:{generation}:{offset}", watched.path.display()),
+    };
+    entry.id = Some(uuid::Uuid::new_v5(&LINE_ID_NAMESPACE, key.as_bytes()).to_string());
+    entry
+        .data
+        .get_or_insert_with(HashMap::new)
+        .insert("file".into(), watched.path.display().to_string().into());
+    entry.dataset = watched.dataset.clone();
+    entry.tags = watched.tags.clone();
+    Some(entry)
+}
+
+/// An object with a `message` or `msg`; its `level` or `severity` and a
+/// numeric `timestamp` in milliseconds are used if present, and the rest of
+/// its fields become `data`. Anything else is sent as text.
+fn parse_json_line(line: &str, default_level: LogLevel) -> Option<LogEntry> {
+    let serde_json::Value::Object(mut fields) = serde_json::from_str(line).ok()? else {
+        return None;
+    };
+    let message = take_string(&mut fields, &["message", "msg"])?;
+    let level = take_string(&mut fields, &["level", "severity"]).map(|level| {
+        level.parse().unwrap_or_else(|_| {
+            // Kept rather than dropped, as it isn't one the API knows.
+            fields.insert("level".into(), level.into());
+            default_level
+        })
+    });
+    let timestamp = fields.get("timestamp").and_then(serde_json::Value::as_u64);
+    if timestamp.is_some() {
+        fields.remove("timestamp");
+    }
+    Some(LogEntry {
+        level: level.unwrap_or(default_level),
+        message,
+        timestamp,
+        data: (!fields.is_empty()).then(|| fields.into_iter().collect()),
+        ..Default::default()
+    })
+}
+
+fn take_string(
+    fields: &mut serde_json::Map<String, serde_json::Value>,
+    keys: &[&str],
+) -> Option<String> {
+    keys.iter().find_map(|key| match fields.get(*key) {
+        Some(serde_json::Value::String(_)) => match fields.remove(*key) {
+            Some(serde_json::Value::String(value)) => Some(value),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// RFC 5424 (`<PRI>1 TIMESTAMP HOST APP PROCID MSGID SD MSG`) or BSD
+/// (`<PRI>Mmm dd hh:mm:ss HOST TAG: MSG`). The level comes from the
+/// severity in `PRI`; the host and app name go into `data`.
+fn parse_syslog_line(line: &str) -> Option<LogEntry> {
+    let (pri, rest) = line.strip_prefix('<')?.split_once('>')?;
+    let pri: u8 = pri.parse().ok()?;
+    let level = match pri % 8 {
+        0..=2 => LogLevel::Fatal,
+        3 => LogLevel::Error,
+        4 => LogLevel::Warn,
+        5 | 6 => LogLevel::Info,
+        _ => LogLevel::Debug,
+    };
+
+    let (hostname, app_name, message) = if let Some(rest) = rest.strip_prefix("1 ") {
+        let mut parts = rest.splitn(6, ' ');
+        let _timestamp = parts.next();
+        let hostname = parts.next();
+        let app_name = parts.next();
+        let _proc_id = parts.next();
+        let _msg_id = parts.next();
+        let message = skip_structured_data(parts.next().unwrap_or(""));
+        (hostname, app_name, message.trim_start_matches('\u{feff}'))
+    } else {
+        // The BSD timestamp is always 15 characters, e.g. `Oct  1 09:30:00`.
+        let rest = rest
+            .get(16..)
+            .filter(|_| rest.as_bytes().get(15) == Some(&b' '))?;
+        let (hostname, rest) = rest.split_once(' ')?;
+        match rest.split_once(": ") {
+            Some((tag, message)) => {
+                let app_name = tag.split_once('[').map_or(tag, |(name, _)| name);
+                (Some(hostname), Some(app_name), message)
+            }
+            None => (Some(hostname), None, rest),
+        }
+    };
+
+    let data: HashMap<String, serde_json::Value> = [("hostname", hostname), ("appName", app_name)]
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = value.filter(|value| *value != "-" && !value.is_empty())?;
+            Some((key.to_string(), value.into()))
+        })
+        .collect();
+    Some(LogEntry {
+        level,
+        message: message.to_string(),
+        data: (!data.is_empty()).then_some(data),
+        ..Default::default()
+    })
+}
+
+/// Skips RFC 5424 structured data: `-`, or one or more `[...]` elements in
+/// which `\]` is escaped.
+fn skip_structured_data(rest: &str) -> &str {
+    if let Some(message) = rest.strip_prefix('-') {
+        return message.strip_prefix(' ').unwrap_or(message);
+    }
+    let mut escaped = false;
+    let mut in_element = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_element => escaped = true,
+            '[' if !in_element => in_element = true,
+            ']' if in_element => in_element = false,
+            _ if in_element => {}
+            ' ' => return &rest[i + 1..],
+            _ => return &rest[i..],
+        }
+    }
+    ""
+}
+
+fn truncate(message: &mut String, max: usize) {
+    if message.len() > max {
+        let mut end = max;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+    }
+}
@@ -0,0 +1,459 @@
+//! Command-line client for shell scripts and cron jobs.
+//!
+//! Configured with `TIMBERLOGS_API_KEY`, `TIMBERLOGS_BASE_URL`,
+//! `TIMBERLOGS_SOURCE`, `TIMBERLOGS_ENVIRONMENT` and `TIMBERLOGS_DATASET`,
+//! or the matching flags before the subcommand.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+use clap::{Args, Parser, Subcommand};
+use futures_util::StreamExt;
+use timberlogs::{
+    shutdown_signal, CallbackDeadLetterSink, Environment, FileAgent, FileAgentConfig, FlowQuery,
+    IngestRawOptions, LogEntry, LogLevel, LogQuery, LogRecord, QueryClient, QueryConfig, RawFormat,
+    TimberlogsClient, TimberlogsConfig, TimberlogsError, WatchedFile,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "timberlogs",
+    version,
+    about = "Send, ingest and tail Timberlogs logs"
+)]
+struct Cli {
+    #[arg(long, env = "TIMBERLOGS_API_KEY", hide_env_values = true)]
+    api_key: String,
+    #[arg(long, env = "TIMBERLOGS_BASE_URL")]
+    base_url: Option<String>,
+    /// Source recorded on logs sent from this host.
+    #[arg(long, env = "TIMBERLOGS_SOURCE", default_value = "timberlogs-cli")]
+    source: String,
+    #[arg(long, env = "TIMBERLOGS_ENVIRONMENT", default_value = "production")]
+    environment: Environment,
+    #[arg(long, env = "TIMBERLOGS_DATASET")]
+    dataset: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send a single log.
+    Send(SendArgs),
+    /// Send a file, or stdin, in one of the raw formats.
+    Ingest {
+        /// Path to read, or `-` for stdin.
+        #[arg(default_value = "-")]
+        file: String,
+        /// json, jsonl, syslog, text, csv or obl.
+        #[arg(long, default_value = "jsonl")]
+        format: RawFormat,
+        /// Level for lines that don't carry one.
+        #[arg(long)]
+        level: Option<LogLevel>,
+    },
+    /// Print logs as they arrive until interrupted.
+    Tail {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Resume after the log with this id.
+        #[arg(long)]
+        cursor: Option<String>,
+        /// Print each log as a line of JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Create, log to and inspect flows.
+    #[command(subcommand)]
+    Flow(FlowCommand),
+    /// Follow log files and ship their new lines until interrupted.
+    Agent {
+        /// A file of plain text lines. Repeatable.
+        #[arg(long = "text", value_name = "PATH")]
+        text: Vec<PathBuf>,
+        /// A file of JSON objects, one per line. Repeatable.
+        #[arg(long = "jsonl", value_name = "PATH")]
+        jsonl: Vec<PathBuf>,
+        /// A file of RFC 5424 or BSD syslog lines. Repeatable.
+        #[arg(long = "syslog", value_name = "PATH")]
+        syslog: Vec<PathBuf>,
+        /// Level for lines that don't carry one.
+        #[arg(long, default_value = "info")]
+        level: LogLevel,
+        /// Where read offsets are kept between runs.
+        #[arg(long, default_value = "timberlogs-agent.json")]
+        checkpoint: PathBuf,
+        #[arg(long, default_value_t = 1000)]
+        poll_ms: u64,
+        /// Ship what files already contain, not only lines written from now on.
+        #[arg(long)]
+        from_start: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum FlowCommand {
+    /// Create a flow and print its id.
+    Start { name: String },
+    /// Log a step of a flow created with `flow start`.
+    Log {
+        flow_id: String,
+        step_index: u32,
+        #[command(flatten)]
+        log: SendArgs,
+    },
+    /// Print a flow's steps in order.
+    Show {
+        flow_id: String,
+        #[arg(long)]
+        json: bool,
+    },
+    /// List flows, newest first.
+    List {
+        #[arg(long)]
+        name: Option<String>,
+        /// Only flows created at or after this time, in Unix milliseconds.
+        #[arg(long)]
+        since: Option<u64>,
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Args)]
+struct SendArgs {
+    message: String,
+    #[arg(long, default_value = "info")]
+    level: LogLevel,
+    /// `key=value`, repeatable. Values that parse as JSON are sent as JSON.
+    #[arg(long = "data", value_name = "KEY=VALUE", value_parser = parse_data)]
+    data: Vec<(String, serde_json::Value)>,
+    /// Repeatable.
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    #[arg(long)]
+    user_id: Option<String>,
+    #[arg(long)]
+    session_id: Option<String>,
+    #[arg(long)]
+    request_id: Option<String>,
+}
+
+#[derive(Args)]
+struct FilterArgs {
+    /// Repeatable; any of the given levels.
+    #[arg(long = "level")]
+    levels: Vec<LogLevel>,
+    #[arg(long)]
+    source: Option<String>,
+    #[arg(long)]
+    environment: Option<Environment>,
+    #[arg(long)]
+    user_id: Option<String>,
+    #[arg(long)]
+    session_id: Option<String>,
+    #[arg(long)]
+    flow_id: Option<String>,
+    /// Repeatable; logs must carry every tag.
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Case-insensitive text in the message or error fields.
+    #[arg(long)]
+    search: Option<String>,
+}
+
+fn parse_data(arg: &str) -> Result<(String, serde_json::Value), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got {arg}"))?;
+    let value = serde_json::from_str(value).unwrap_or_else(|_| value.into());
+    Ok((key.to_string(), value))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("timberlogs: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), TimberlogsError> {
+    match cli.command {
+        Command::Send(ref args) => send(&cli, entry(args)).await,
+        Command::Ingest {
+            ref file,
+            format,
+            level,
+        } => {
+            let mut body = String::new();
+            if file == "-" {
+                std::io::stdin().read_to_string(&mut body)?;
+            } else {
+                body = std::fs::read_to_string(file)?;
+            }
+            let options = IngestRawOptions {
+                source: Some(cli.source.clone()),
+                environment: Some(cli.environment.clone()),
+                level,
+                dataset: cli.dataset.clone(),
+            };
+            client(&cli).ingest_raw(body, format, Some(options)).await
+        }
+        Command::Tail {
+            ref filter,
+            ref cursor,
+            json,
+        } => {
+            let query = LogQuery {
+                cursor: cursor.clone(),
+                ..log_query(filter)
+            };
+            let reader = reader(&cli)?;
+            let mut tail = std::pin::pin!(reader.tail(query));
+            while let Some(log) = tail.next().await {
+                print_log(&log?, json);
+            }
+            Ok(())
+        }
+        Command::Flow(FlowCommand::Start { ref name }) => {
+            let client = client(&cli);
+            let flow = client.flow(name.as_str()).await?;
+            println!("{}", flow.id);
+            Ok(())
+        }
+        Command::Flow(FlowCommand::Log {
+            ref flow_id,
+            step_index,
+            ref log,
+        }) => {
+            let entry = LogEntry {
+                flow_id: Some(flow_id.clone()),
+                step_index: Some(step_index),
+                ..entry(log)
+            };
+            send(&cli, entry).await
+        }
+        Command::Flow(FlowCommand::Show { ref flow_id, json }) => {
+            let Some(flow) = reader(&cli)?.get_flow(flow_id).await? else {
+                return Err(TimberlogsError::Validation(format!(
+                    "flow not found: {flow_id}"
+                )));
+            };
+            if !json {
+                let created = format_timestamp(flow.created_at);
+                println!("{} ({}), created {created}", flow.name, flow.flow_id);
+            }
+            for step in &flow.steps {
+                print_log(step, json);
+            }
+            Ok(())
+        }
+        Command::Flow(FlowCommand::List {
+            ref name,
+            since,
+            limit,
+            json,
+        }) => {
+            let query = FlowQuery {
+                name: name.clone(),
+                from: since,
+                limit: Some(limit),
+                ..Default::default()
+            };
+            for flow in reader(&cli)?.list_flows(&query).await?.flows {
+                if json {
+                    println!("{}", serde_json::to_string(&flow).unwrap_or_default());
+                } else {
+                    println!(
+                        "{}  {}  {}  {} steps",
+                        format_timestamp(flow.created_at),
+                        flow.flow_id,
+                        flow.name,
+                        flow.step_count
+                    );
+                }
+            }
+            Ok(())
+        }
+        Command::Agent {
+            ref text,
+            ref jsonl,
+            ref syslog,
+            level,
+            ref checkpoint,
+            poll_ms,
+            from_start,
+        } => {
+            let watched = |paths: &[PathBuf], format: RawFormat| {
+                paths
+                    .iter()
+                    .map(|path| WatchedFile {
+                        level,
+                        ..WatchedFile::new(path, format)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let files = [
+                watched(text, RawFormat::Text),
+                watched(jsonl, RawFormat::Jsonl),
+                watched(syslog, RawFormat::Syslog),
+            ]
+            .concat();
+            // Errors would otherwise vanish while the agent keeps running. A
+            // refused API key won't start working, so that one stops it.
+            let (fatal, mut fatal_errors) = tokio::sync::mpsc::unbounded_channel();
+            let client = Arc::new(TimberlogsClient::new(TimberlogsConfig {
+                on_error: Some(Box::new(move |e: &TimberlogsError| {
+                    eprintln!("timberlogs agent: {e}");
+                    if let TimberlogsError::Http {
+                        status: status @ (401 | 403),
+                        body,
+                    } = e
+                    {
+                        let _ = fatal.send(TimberlogsError::Http {
+                            status: *status,
+                            body: body.clone(),
+                        });
+                    }
+                })),
+                ..config(&cli)
+            }));
+            let mut agent = FileAgent::new(
+                Arc::clone(&client),
+                FileAgentConfig {
+                    files,
+                    checkpoint_path: checkpoint.clone(),
+                    poll_interval_ms: Some(poll_ms),
+                    read_existing: from_start,
+                },
+            )?;
+            tokio::select! {
+                _ = agent.run() => {}
+                _ = shutdown_signal() => {}
+                Some(e) = fatal_errors.recv() => return Err(e),
+            }
+            agent.checkpoint().await
+        }
+    }
+}
+
+/// Sends one log and waits for it to be accepted. A log the server rejects is
+/// dead-lettered rather than failing the flush, so it's caught here.
+async fn send(cli: &Cli, entry: LogEntry) -> Result<(), TimberlogsError> {
+    let rejected = Arc::new(Mutex::new(None));
+    let sink = {
+        let rejected = Arc::clone(&rejected);
+        CallbackDeadLetterSink::new(move |_: &_, e: &TimberlogsError| {
+            *rejected.lock().unwrap() = Some(e.to_string());
+        })
+    };
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        dead_letter: Some(Arc::new(sink)),
+        ..config(cli)
+    });
+    client.log(entry).await?;
+    client.flush().await?;
+    let rejected = rejected.lock().unwrap().take();
+    match rejected {
+        Some(error) => Err(TimberlogsError::Validation(format!(
+            "log was rejected: {error}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn client(cli: &Cli) -> TimberlogsClient {
+    TimberlogsClient::new(config(cli))
+}
+
+fn config(cli: &Cli) -> TimberlogsConfig {
+    TimberlogsConfig {
+        source: cli.source.clone(),
+        environment: cli.environment.clone(),
+        api_key: cli.api_key.clone(),
+        base_url: cli.base_url.clone(),
+        dataset: cli.dataset.clone(),
+        ..Default::default()
+    }
+}
+
+fn reader(cli: &Cli) -> Result<QueryClient, TimberlogsError> {
+    QueryClient::new(QueryConfig {
+        api_key: cli.api_key.clone(),
+        base_url: cli.base_url.clone(),
+        ..Default::default()
+    })
+}
+
+fn entry(args: &SendArgs) -> LogEntry {
+    LogEntry {
+        level: args.level,
+        message: args.message.clone(),
+        data: (!args.data.is_empty()).then(|| args.data.iter().cloned().collect::<HashMap<_, _>>()),
+        tags: (!args.tags.is_empty()).then(|| args.tags.clone()),
+        user_id: args.user_id.clone(),
+        session_id: args.session_id.clone(),
+        request_id: args.request_id.clone(),
+        ..Default::default()
+    }
+}
+
+fn log_query(filter: &FilterArgs) -> LogQuery {
+    LogQuery {
+        levels: filter.levels.clone(),
+        source: filter.source.clone(),
+        environment: filter.environment.clone(),
+        user_id: filter.user_id.clone(),
+        session_id: filter.session_id.clone(),
+        flow_id: filter.flow_id.clone(),
+        tags: filter.tags.clone(),
+        search: filter.search.clone(),
+        ..Default::default()
+    }
+}
+
+fn print_log(log: &LogRecord, json: bool) {
+    if json {
+        println!("{}", serde_json::to_string(log).unwrap_or_default());
+        return;
+    }
+    let level = log.level.as_str().to_ascii_uppercase();
+    let step = log.step_index.map(|i| format!(" #{i}")).unwrap_or_default();
+    println!(
+        "{} {level:<5} [{}]{step} {}",
+        format_timestamp(log.timestamp),
+        log.source,
+        log.message
+    );
+}
+
+/// RFC 3339 in UTC with milliseconds, e.g. `2024-05-01T12:30:00.250Z`.
+fn format_timestamp(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (hour, minute, second) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}.{:03}Z",
+        ms % 1000
+    )
+}
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc as std_mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+use crate::client::{ShutdownReport, TimberlogsClient, TimberlogsConfig};
+use crate::error::TimberlogsError;
+use crate::stats::Stats;
+use crate::types::{CreateLogArgs, IngestRawOptions, LogEntry, LogLevel, RawFormat, VerifyReport};
+
+type Job =
+    Box<dyn FnOnce(Arc<TimberlogsClient>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+enum Message {
+    Log(Box<CreateLogArgs>),
+    Job(Job),
+    Disconnect(std_mpsc::Sender<Result<(), TimberlogsError>>),
+}
+
+/// A synchronous client for applications without an async runtime.
+///
+/// A dedicated background thread runs a [`TimberlogsClient`] on its own
+/// single-threaded runtime, which does the queueing, batching and flushing.
+/// Logging checks the level and validates the entry on the calling thread,
+/// then hands it over without waiting, so a slow backend never holds up
+/// callers. Other calls such as `flush` block until that thread has handled
+/// them. None of them may be made from inside an async context.
+pub struct BlockingTimberlogsClient {
+    client: Option<Arc<TimberlogsClient>>,
+    sender: Option<mpsc::UnboundedSender<Message>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BlockingTimberlogsClient {
+    pub fn new(config: TimberlogsConfig) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Message>();
+        let (ready, started) = std_mpsc::channel();
+
+        let thread = std::thread::Builder::new()
+            .name("timberlogs".into())
+            .spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("failed to build timberlogs runtime");
+                runtime.block_on(async move {
+                    let client = Arc::new(TimberlogsClient::new(config));
+                    let _ = ready.send(Arc::clone(&client));
+
+                    // Messages run one at a time so entries keep their call order.
+                    while let Some(message) = receiver.recv().await {
+                        match message {
+                            Message::Log(args) => {
+                                if let Err(e) = client.enqueue(*args).await {
+                                    client.report_error(&e);
+                                }
+                            }
+                            Message::Job(job) => job(Arc::clone(&client)).await,
+                            Message::Disconnect(done) => {
+                                let result = match Arc::try_unwrap(client) {
+                                    Ok(mut client) => client.disconnect().await,
+                                    Err(client) => client.flush().await,
+                                };
+                                let _ = done.send(result);
+                                return;
+                            }
+                        }
+                    }
+                });
+            })
+            .expect("failed to spawn timberlogs thread");
+
+        // Surface config panics from `TimberlogsClient::new` on the caller's thread.
+        let Ok(client) = started.recv() else {
+            if let Err(panic) = thread.join() {
+                std::panic::resume_unwind(panic);
+            }
+            unreachable!("timberlogs thread exited before starting");
+        };
+
+        Self {
+            client: Some(client),
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    /// Validates `entry` here and queues it on the background thread without
+    /// waiting. Returns whether it passed the level filter.
+    fn send_log(&self, entry: LogEntry) -> Result<bool, TimberlogsError> {
+        let (Some(client), Some(sender)) = (&self.client, &self.sender) else {
+            return Err(TimberlogsError::NotConnected);
+        };
+        let Some(args) = client.prepare_blocking(entry)? else {
+            return Ok(false);
+        };
+        sender
+            .send(Message::Log(Box::new(args)))
+            .map_err(|_| TimberlogsError::NotConnected)?;
+        Ok(true)
+    }
+
+    /// Stops the background thread after handing it `message`, if any. The
+    /// thread must hold the last reference to the client so it is dropped or
+    /// disconnected there.
+    fn stop(&mut self, message: Option<Message>) {
+        self.client.take();
+        if let (Some(sender), Some(message)) = (self.sender.take(), message) {
+            let _ = sender.send(message);
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    fn run<T, F, Fut>(&self, f: F) -> Result<T, TimberlogsError>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<TimberlogsClient>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, TimberlogsError>> + Send + 'static,
+    {
+        let sender = self.sender.as_ref().ok_or(TimberlogsError::NotConnected)?;
+        let (reply, result) = std_mpsc::channel();
+        let job: Job = Box::new(move |client| {
+            Box::pin(async move {
+                let _ = reply.send(f(client).await);
+            })
+        });
+        sender
+            .send(Message::Job(job))
+            .map_err(|_| TimberlogsError::NotConnected)?;
+        result.recv().map_err(|_| TimberlogsError::NotConnected)?
+    }
+
+    // Levels and ids are read when an entry is validated on the calling
+    // thread, so they are changed there too.
+
+    pub fn min_level(&self) -> LogLevel {
+        self.client.as_ref().map_or(LogLevel::Trace, |client| {
+            client.levels_blocking().min_level()
+        })
+    }
+
+    pub fn set_min_level(&self, level: LogLevel) {
+        if let Some(ref client) = self.client {
+            client.levels_blocking().set_min_level(level);
+        }
+    }
+
+    pub fn set_level_override(&self, prefix: impl Into<String>, level: Option<LogLevel>) {
+        if let Some(ref client) = self.client {
+            client.levels_blocking().set_override(prefix.into(), level);
+        }
+    }
+
+    pub fn set_user_id(&self, user_id: Option<String>) {
+        if let Some(ref client) = self.client {
+            client.set_user_id_blocking(user_id);
+        }
+    }
+
+    pub fn set_session_id(&self, session_id: Option<String>) {
+        if let Some(ref client) = self.client {
+            client.set_session_id_blocking(session_id);
+        }
+    }
+
+    pub fn trace(
+        &self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log_with_level(LogLevel::Trace, message, data)
+    }
+
+    pub fn debug(
+        &self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log_with_level(LogLevel::Debug, message, data)
+    }
+
+    pub fn info(
+        &self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log_with_level(LogLevel::Info, message, data)
+    }
+
+    pub fn warn(
+        &self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log_with_level(LogLevel::Warn, message, data)
+    }
+
+    pub fn error(
+        &self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log_with_level(LogLevel::Error, message, data)
+    }
+
+    pub fn fatal(
+        &self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log_with_level(LogLevel::Fatal, message, data)
+    }
+
+    fn log_with_level(
+        &self,
+        level: LogLevel,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log(LogEntry {
+            level,
+            message: message.into(),
+            data,
+            ..Default::default()
+        })
+    }
+
+    /// Returns once the entry is validated and handed to the background
+    /// thread. Errors from sending it later go to `on_error`.
+    pub fn log(&self, entry: LogEntry) -> Result<(), TimberlogsError> {
+        self.send_log(entry).map(|_| ())
+    }
+
+    pub fn flow(&self, name: impl Into<String>) -> Result<BlockingFlow<'_>, TimberlogsError> {
+        let name = name.into();
+        let (id, name) = self.run(move |client| async move {
+            let flow = client.flow(name).await?;
+            Ok((flow.id, flow.name))
+        })?;
+        Ok(BlockingFlow {
+            id,
+            name,
+            step_index: 0,
+            client: self,
+        })
+    }
+
+    pub fn ingest_raw(
+        &self,
+        body: impl Into<String>,
+        format: RawFormat,
+        options: Option<IngestRawOptions>,
+    ) -> Result<(), TimberlogsError> {
+        let body = body.into();
+        self.run(move |client| async move { client.ingest_raw(body, format, options).await })
+    }
+
+    pub fn verify(&self) -> Result<VerifyReport, TimberlogsError> {
+        self.run(|client| async move { client.verify().await })
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.run(|client| async move { Ok(client.stats().await) })
+            .unwrap_or_default()
+    }
+
+    pub fn flush(&self) -> Result<(), TimberlogsError> {
+        self.run(|client| async move { client.flush().await })
+    }
+
+    /// Flushes remaining logs and stops the background thread.
+    pub fn disconnect(&mut self) -> Result<(), TimberlogsError> {
+        if self.sender.is_none() {
+            return Ok(());
+        }
+        let (done, result) = std_mpsc::channel();
+        self.stop(Some(Message::Disconnect(done)));
+        result.recv().unwrap_or(Err(TimberlogsError::NotConnected))
+    }
+
+    /// Like [`TimberlogsClient::shutdown`], then stops the background thread.
+    pub fn shutdown(&mut self, timeout: Duration) -> ShutdownReport {
+        let report = self
+            .run(move |client| async move { Ok(client.shutdown(timeout).await) })
+            .unwrap_or_default();
+        self.stop(None);
+        report
+    }
+}
+
+impl Drop for BlockingTimberlogsClient {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread, which drops the client like
+        // dropping a `TimberlogsClient` does.
+        self.stop(None);
+    }
+}
+
+pub struct BlockingFlow<'a> {
+    pub id: String,
+    pub name: String,
+    step_index: u32,
+    client: &'a BlockingTimberlogsClient,
+}
+
+impl<'a> BlockingFlow<'a> {
+    pub fn step_index(&self) -> u32 {
+        self.step_index
+    }
+
+    pub fn trace(
+        &mut self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        self.log_with_level(LogLevel::Trace, message, data, None)
+    }
+
+    pub fn debug(
+        &mut self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        self.log_with_level(LogLevel::Debug, message, data, None)
+    }
+
+    pub fn info(
+        &mut self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        self.log_with_level(LogLevel::Info, message, data, None)
+    }
+
+    pub fn warn(
+        &mut self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        self.log_with_level(LogLevel::Warn, message, data, None)
+    }
+
+    pub fn error(
+        &mut self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        self.log_with_level(LogLevel::Error, message, data, None)
+    }
+
+    pub fn fatal(
+        &mut self,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        self.log_with_level(LogLevel::Fatal, message, data, None)
+    }
+
+    pub fn log_with_level(
+        &mut self,
+        level: LogLevel,
+        message: impl Into<String>,
+        data: Option<HashMap<String, serde_json::Value>>,
+        tags: Option<Vec<String>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        let entry = LogEntry {
+            level,
+            message: message.into(),
+            data,
+            tags,
+            flow_id: Some(self.id.clone()),
+            step_index: Some(self.step_index),
+            ..Default::default()
+        };
+        // Filtered steps don't use up an index, matching `Flow`.
+        if self.client.send_log(entry)? {
+            self.step_index += 1;
+        }
+        Ok(self)
+    }
+}
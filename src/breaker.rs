@@ -0,0 +1,185 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::TimberlogsError;
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_OPEN_DURATION_MS: u64 = 30000;
+const DEFAULT_SUCCESS_THRESHOLD: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests go through.
+    Closed,
+    /// Requests fail with `TimberlogsError::CircuitOpen` without being sent.
+    Open,
+    /// One trial request at a time goes through to see if the API is back.
+    HalfOpen,
+}
+
+/// Called with the old and new state whenever the circuit changes state.
+pub type CircuitCallback = Box<dyn Fn(CircuitState, CircuitState) + Send + Sync>;
+
+/// Stops calling the ingest API while it is failing.
+///
+/// After `failure_threshold` consecutive failed attempts (network errors,
+/// 429s, 5xxs and the like) the circuit opens and every request fails fast.
+/// Queued logs stay in the queue meanwhile. After `open_duration_ms` a trial
+/// request is let through; `success_threshold` successful trials close the
+/// circuit again and any failure reopens it.
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub open_duration_ms: u64,
+    pub success_threshold: u32,
+    pub on_state_change: Option<CircuitCallback>,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            open_duration_ms: DEFAULT_OPEN_DURATION_MS,
+            success_threshold: DEFAULT_SUCCESS_THRESHOLD,
+            on_state_change: None,
+        }
+    }
+}
+
+struct BreakerState {
+    state: CircuitState,
+    failures: u32,
+    successes: u32,
+    opened_at: Instant,
+    // Set while a half-open trial is out. A trial that never reports back
+    // (its future was dropped) stops blocking others after `open_duration`.
+    trial_started: Option<Instant>,
+}
+
+pub(crate) struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    success_threshold: u32,
+    on_state_change: Option<CircuitCallback>,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Result<Self, TimberlogsError> {
+        if config.failure_threshold == 0 {
+            return Err(TimberlogsError::Validation(
+                "circuit breaker failure_threshold must be greater than 0".into(),
+            ));
+        }
+        if config.success_threshold == 0 {
+            return Err(TimberlogsError::Validation(
+                "circuit breaker success_threshold must be greater than 0".into(),
+            ));
+        }
+        Ok(Self {
+            failure_threshold: config.failure_threshold,
+            open_duration: Duration::from_millis(config.open_duration_ms),
+            success_threshold: config.success_threshold,
+            on_state_change: config.on_state_change,
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                failures: 0,
+                successes: 0,
+                opened_at: Instant::now(),
+                trial_started: None,
+            }),
+        })
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state.lock().unwrap().state
+    }
+
+    /// Whether a request may be sent now. Every `true` must be followed by
+    /// [`record_success`](Self::record_success) or
+    /// [`record_failure`](Self::record_failure).
+    pub fn try_acquire(&self) -> bool {
+        let now = Instant::now();
+        let mut guard = self.state.lock().unwrap();
+        let change = match guard.state {
+            CircuitState::Closed => return true,
+            CircuitState::Open if now.duration_since(guard.opened_at) < self.open_duration => {
+                return false;
+            }
+            CircuitState::Open => {
+                guard.successes = 0;
+                self.transition(&mut guard, CircuitState::HalfOpen)
+            }
+            CircuitState::HalfOpen => None,
+        };
+        let allowed = match guard.trial_started {
+            Some(started) if now.duration_since(started) < self.open_duration => false,
+            _ => {
+                guard.trial_started = Some(now);
+                true
+            }
+        };
+        drop(guard);
+        self.notify(change);
+        allowed
+    }
+
+    pub fn record_success(&self) {
+        let mut guard = self.state.lock().unwrap();
+        guard.failures = 0;
+        let change = match guard.state {
+            CircuitState::HalfOpen => {
+                guard.trial_started = None;
+                guard.successes += 1;
+                if guard.successes >= self.success_threshold {
+                    self.transition(&mut guard, CircuitState::Closed)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        drop(guard);
+        self.notify(change);
+    }
+
+    pub fn record_failure(&self) {
+        let mut guard = self.state.lock().unwrap();
+        let change = match guard.state {
+            CircuitState::Closed => {
+                guard.failures += 1;
+                if guard.failures >= self.failure_threshold {
+                    self.open(&mut guard)
+                } else {
+                    None
+                }
+            }
+            CircuitState::HalfOpen => self.open(&mut guard),
+            CircuitState::Open => None,
+        };
+        drop(guard);
+        self.notify(change);
+    }
+
+    fn open(&self, state: &mut BreakerState) -> Option<(CircuitState, CircuitState)> {
+        state.opened_at = Instant::now();
+        state.trial_started = None;
+        state.failures = 0;
+        self.transition(state, CircuitState::Open)
+    }
+
+    fn transition(
+        &self,
+        state: &mut BreakerState,
+        to: CircuitState,
+    ) -> Option<(CircuitState, CircuitState)> {
+        let from = std::mem::replace(&mut state.state, to);
+        (from != to).then_some((from, to))
+    }
+
+    // Runs outside the lock so the callback may inspect the client.
+    fn notify(&self, change: Option<(CircuitState, CircuitState)>) {
+        if let (Some((from, to)), Some(cb)) = (change, &self.on_state_change) {
+            cb(from, to);
+        }
+    }
+}
@@ -1,15 +1,36 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::future::{select, Either};
 use tokio::sync::Mutex;
-use tokio::time::{interval, Duration};
+#[cfg(feature = "blocking")]
+use tokio::sync::MutexGuard;
 
+use crate::breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use crate::dead_letter::{read_dead_letters, DeadLetterSink};
 use crate::error::TimberlogsError;
-use crate::types::{BatchPayload, CreateLogArgs, Environment, FlowResponse, IngestRawOptions, IngestResponse, LogEntry, LogLevel, RawFormat};
-
-const DEFAULT_BASE_URL: &str = "https://timberlogs-ingest.enaboapps.workers.dev";
-const LOGS_PATH: &str = "/v1/logs";
-const FLOWS_PATH: &str = "/v1/flows";
+use crate::executor::{Executor, TaskHandle, TokioExecutor};
+use crate::levels::{LevelDirectives, LevelFileConfig, LevelFilter};
+#[cfg(feature = "otlp")]
+use crate::otlp::{OtlpConfig, OtlpTransport};
+use crate::sampling::{Sampler, SamplingConfig};
+use crate::stats::{Counters, Stats};
+use crate::trace::TraceContext;
+use crate::transport::{HttpTransport, Transport};
+use crate::types::{
+    CreateLogArgs, Environment, IngestRawOptions, LogEntry, LogLevel, RawFormat, VerifyReport,
+};
+
+pub(crate) const DEFAULT_BASE_URL: &str = "https://timberlogs-ingest.enaboapps.workers.dev";
 
 const DEFAULT_BATCH_SIZE: usize = 10;
+const DEFAULT_MAX_BATCH_BYTES: usize = 1_000_000;
+// `{"logs":[` and `]}` around the entries.
+const BATCH_ENVELOPE_BYTES: usize = 11;
 const DEFAULT_FLUSH_INTERVAL_MS: u64 = 5000;
 const DEFAULT_MAX_RETRIES: u32 = 3;
 const DEFAULT_INITIAL_DELAY_MS: u64 = 1000;
@@ -33,6 +54,13 @@ impl Default for RetryConfig {
 
 pub type ErrorCallback = Box<dyn Fn(&TimberlogsError) + Send + Sync>;
 
+/// What happened to the queued entries during [`TimberlogsClient::shutdown`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub sent: usize,
+    pub dropped: usize,
+}
+
 pub struct TimberlogsConfig {
     pub source: String,
     pub environment: Environment,
@@ -42,10 +70,22 @@ pub struct TimberlogsConfig {
     pub session_id: Option<String>,
     pub dataset: Option<String>,
     pub batch_size: Option<usize>,
+    pub max_batch_bytes: Option<usize>,
     pub flush_interval_ms: Option<u64>,
     pub min_level: Option<LogLevel>,
+    pub level_overrides: Option<HashMap<String, LogLevel>>,
+    pub level_file: Option<LevelFileConfig>,
+    pub legacy_levels: Option<bool>,
     pub retry: Option<RetryConfig>,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
     pub on_error: Option<ErrorCallback>,
+    pub dead_letter: Option<Arc<dyn DeadLetterSink>>,
+    pub flush_on_drop: Option<bool>,
+    pub sampling: Option<SamplingConfig>,
+    #[cfg(feature = "otlp")]
+    pub otlp: Option<OtlpConfig>,
+    pub transport: Option<Arc<dyn Transport>>,
+    pub executor: Option<Arc<dyn Executor>>,
     #[doc(hidden)]
     pub base_url: Option<String>,
 }
@@ -61,10 +101,22 @@ impl Default for TimberlogsConfig {
             session_id: None,
             dataset: None,
             batch_size: None,
+            max_batch_bytes: None,
             flush_interval_ms: None,
             min_level: None,
+            level_overrides: None,
+            level_file: None,
+            legacy_levels: None,
             retry: None,
+            circuit_breaker: None,
             on_error: None,
+            dead_letter: None,
+            flush_on_drop: None,
+            sampling: None,
+            #[cfg(feature = "otlp")]
+            otlp: None,
+            transport: None,
+            executor: None,
             base_url: None,
         }
     }
@@ -72,129 +124,170 @@ impl Default for TimberlogsConfig {
 
 struct ClientInner {
     queue: Vec<CreateLogArgs>,
-    http: reqwest::Client,
+    sampler: Option<Sampler>,
 }
 
 pub struct TimberlogsClient {
     config: Arc<ClientConfig>,
     inner: Arc<Mutex<ClientInner>>,
-    flush_handle: Option<tokio::task::JoinHandle<()>>,
+    flush_handle: Option<TaskHandle>,
+    reload_handle: Option<TaskHandle>,
 }
 
 struct ClientConfig {
     source: String,
     environment: Environment,
-    api_key: String,
     version: Option<String>,
     user_id: Mutex<Option<String>>,
     session_id: Mutex<Option<String>>,
     dataset: Option<String>,
     batch_size: usize,
-    min_level: LogLevel,
+    max_batch_bytes: usize,
+    levels: Mutex<LevelFilter>,
+    level_file: Option<LevelFileConfig>,
+    legacy_levels: bool,
     retry: RetryConfig,
+    breaker: Option<CircuitBreaker>,
     on_error: Option<ErrorCallback>,
-    base_url: String,
+    dead_letter: Option<Arc<dyn DeadLetterSink>>,
+    flush_on_drop: bool,
+    transport: Arc<dyn Transport>,
+    executor: Arc<dyn Executor>,
+    // Held while a batch is out of the queue, so shutdown can wait for it.
+    flush_lock: Mutex<()>,
+    closed: AtomicBool,
+    stats: Counters,
 }
 
-fn check_str(value: Option<&str>, name: &str, max_len: usize) -> Result<(), TimberlogsError> {
-    if let Some(v) = value {
-        if v.len() > max_len {
-            return Err(TimberlogsError::Validation(format!(
-                "{name} exceeds {max_len} characters: {}",
-                v.len()
-            )));
+impl ClientConfig {
+    fn report_error(&self, error: &TimberlogsError) {
+        if let Some(ref cb) = self.on_error {
+            cb(error);
         }
     }
-    Ok(())
-}
 
-fn validate_entry(entry: &LogEntry) -> Result<(), TimberlogsError> {
-    if entry.message.is_empty() {
-        return Err(TimberlogsError::Validation(
-            "message must not be empty".into(),
-        ));
-    }
-    if entry.message.len() > 10_000 {
-        return Err(TimberlogsError::Validation(format!(
-            "message exceeds 10000 characters: {}",
-            entry.message.len()
-        )));
-    }
-    check_str(entry.user_id.as_deref(), "user_id", 100)?;
-    check_str(entry.session_id.as_deref(), "session_id", 100)?;
-    check_str(entry.request_id.as_deref(), "request_id", 100)?;
-    check_str(entry.error_name.as_deref(), "error_name", 200)?;
-    check_str(entry.error_stack.as_deref(), "error_stack", 10_000)?;
-    check_str(entry.flow_id.as_deref(), "flow_id", 50)?;
-    check_str(entry.dataset.as_deref(), "dataset", 50)?;
-    check_str(entry.ip_address.as_deref(), "ip_address", 100)?;
-    check_str(entry.country.as_deref(), "country", 10)?;
-    if let Some(ref tags) = entry.tags {
-        if tags.len() > 20 {
-            return Err(TimberlogsError::Validation(format!(
-                "tags must have at most 20 items, got {}",
-                tags.len()
-            )));
-        }
-        for (i, tag) in tags.iter().enumerate() {
-            if tag.len() > 50 {
-                return Err(TimberlogsError::Validation(format!(
-                    "tags[{i}] exceeds 50 characters: {}",
-                    tag.len()
-                )));
-            }
-        }
-    }
-    if let Some(step) = entry.step_index {
-        if step > 1000 {
-            return Err(TimberlogsError::Validation(format!(
-                "step_index must be 0-1000, got {step}"
-            )));
+    /// Hands over an entry that won't be delivered. Without a sink it is at
+    /// least reported to `on_error`.
+    fn dead_letter(&self, log: &CreateLogArgs, error: &TimberlogsError) {
+        self.stats.dropped(1);
+        let Some(ref sink) = self.dead_letter else {
+            self.report_error(error);
+            return;
+        };
+        if let Err(e) = sink.dead_letter(log, error) {
+            self.report_error(&e);
         }
     }
-    Ok(())
 }
 
 impl TimberlogsClient {
+    /// # Panics
+    ///
+    /// If the config is invalid; see [`try_new`](Self::try_new).
     pub fn new(config: TimberlogsConfig) -> Self {
+        Self::try_new(config).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Builds a client, or returns [`TimberlogsError::Validation`] if
+    /// `batch_size` or `max_batch_bytes` is 0, the environment name is
+    /// invalid, a sampling rate, rate limit or circuit breaker threshold is
+    /// out of range, or the client needs to start background tasks and its
+    /// executor can't spawn here. With the default [`TokioExecutor`] that
+    /// means it must be called inside a tokio runtime.
+    pub fn try_new(config: TimberlogsConfig) -> Result<Self, TimberlogsError> {
         let batch_size = config.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
-        assert!(batch_size > 0, "batch_size must be greater than 0");
+        if batch_size == 0 {
+            return Err(TimberlogsError::Validation(
+                "batch_size must be greater than 0".into(),
+            ));
+        }
+        let max_batch_bytes = config.max_batch_bytes.unwrap_or(DEFAULT_MAX_BATCH_BYTES);
+        if max_batch_bytes == 0 {
+            return Err(TimberlogsError::Validation(
+                "max_batch_bytes must be greater than 0".into(),
+            ));
+        }
+        // Also turns `Custom("production")` into `Production`.
+        let environment = match config.environment {
+            Environment::Custom(name) => Environment::custom(name)?,
+            environment => environment,
+        };
+        let sampler = config.sampling.map(Sampler::new).transpose()?;
+        let breaker = config
+            .circuit_breaker
+            .map(CircuitBreaker::new)
+            .transpose()?;
+
+        #[cfg(feature = "otlp")]
+        let transport = config
+            .transport
+            .or_else(|| Some(Arc::new(OtlpTransport::new(config.otlp?)) as Arc<dyn Transport>));
+        #[cfg(not(feature = "otlp"))]
+        let transport = config.transport;
+        // Without an API key the default transport can't send anything.
+        let should_flush = transport.is_some() || !config.api_key.is_empty();
+        let transport = transport.unwrap_or_else(|| {
+            Arc::new(HttpTransport::new(
+                config
+                    .base_url
+                    .unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+                config.api_key,
+            ))
+        });
+        let executor = config.executor.unwrap_or_else(|| Arc::new(TokioExecutor));
+        if (should_flush || config.level_file.is_some()) && !executor.can_spawn() {
+            return Err(TimberlogsError::Validation(
+                "the executor can't spawn background tasks here; create the client inside a tokio runtime or set `executor`".into(),
+            ));
+        }
 
         let client_config = Arc::new(ClientConfig {
+            stats: Counters::new(config.source.clone()),
             source: config.source,
-            environment: config.environment,
-            api_key: config.api_key,
+            environment,
             version: config.version,
             user_id: Mutex::new(config.user_id),
             session_id: Mutex::new(config.session_id),
             dataset: config.dataset,
             batch_size,
-            min_level: config.min_level.unwrap_or(LogLevel::Debug),
+            max_batch_bytes,
+            levels: Mutex::new(LevelFilter::new(
+                config.min_level.unwrap_or(LogLevel::Trace),
+                config.level_overrides.unwrap_or_default(),
+            )),
+            level_file: config.level_file,
+            legacy_levels: config.legacy_levels.unwrap_or(false),
             retry: config.retry.unwrap_or_default(),
+            breaker,
             on_error: config.on_error,
-            base_url: config.base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            dead_letter: config.dead_letter,
+            flush_on_drop: config.flush_on_drop.unwrap_or(false),
+            transport,
+            executor,
+            flush_lock: Mutex::new(()),
+            closed: AtomicBool::new(false),
         });
 
         let inner = Arc::new(Mutex::new(ClientInner {
             queue: Vec::new(),
-            http: reqwest::Client::new(),
+            sampler,
         }));
 
-        let flush_handle = if !client_config.api_key.is_empty() {
+        let flush_handle = if should_flush {
             let flush_interval = config
                 .flush_interval_ms
                 .unwrap_or(DEFAULT_FLUSH_INTERVAL_MS);
 
             let flush_config = Arc::clone(&client_config);
             let flush_inner = Arc::clone(&inner);
-            Some(tokio::spawn(async move {
-                let mut ticker = interval(Duration::from_millis(flush_interval));
+            Some(TaskHandle::spawn(&*client_config.executor, async move {
                 loop {
-                    ticker.tick().await;
-                    if let Err(e) = flush_batch(&flush_config, &flush_inner).await {
-                        if let Some(ref cb) = flush_config.on_error {
-                            cb(&e);
-                        }
+                    flush_config
+                        .executor
+                        .sleep(Duration::from_millis(flush_interval))
+                        .await;
+                    if let Err(e) = flush_batch(&flush_config, &flush_inner, false).await {
+                        flush_config.report_error(&e);
                     }
                 }
             }))
@@ -202,11 +295,79 @@ impl TimberlogsClient {
             None
         };
 
-        Self {
+        let reload_handle = client_config.level_file.as_ref().map(|level_file| {
+            if let Err(e) = load_level_file(&client_config, level_file) {
+                client_config.report_error(&e);
+            }
+            TaskHandle::spawn(
+                &*client_config.executor,
+                watch_level_file(Arc::clone(&client_config)),
+            )
+        });
+
+        Ok(Self {
             config: client_config,
             inner,
             flush_handle,
+            reload_handle,
+        })
+    }
+
+    pub(crate) fn report_error(&self, error: &TimberlogsError) {
+        self.config.report_error(error);
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    pub(crate) fn record_dropped(&self, count: u64) {
+        self.config.stats.dropped(count);
+    }
+
+    pub(crate) fn executor(&self) -> Arc<dyn Executor> {
+        Arc::clone(&self.config.executor)
+    }
+
+    /// Whether an entry at `level` without a target passes the level filter.
+    /// Counts it as filtered if not, so only call it for entries that will be
+    /// skipped on `false`.
+    pub(crate) async fn check_level(&self, level: LogLevel) -> bool {
+        let enabled = level
+            >= self
+                .config
+                .levels
+                .lock()
+                .await
+                .level_for(&self.config.source);
+        if !enabled {
+            self.config.stats.filtered(1);
         }
+        enabled
+    }
+
+    pub async fn min_level(&self) -> LogLevel {
+        self.config.levels.lock().await.min_level()
+    }
+
+    pub async fn set_min_level(&self, level: LogLevel) {
+        self.config.levels.lock().await.set_min_level(level);
+    }
+
+    pub async fn set_level_override(&self, prefix: impl Into<String>, level: Option<LogLevel>) {
+        self.config
+            .levels
+            .lock()
+            .await
+            .set_override(prefix.into(), level);
+    }
+
+    pub async fn reload_level_file(&self) -> Result<(), TimberlogsError> {
+        let Some(ref level_file) = self.config.level_file else {
+            return Err(TimberlogsError::Validation(
+                "no level_file configured".into(),
+            ));
+        };
+        let directives = LevelDirectives::load(&level_file.path)?;
+        self.config.levels.lock().await.apply(directives);
+        Ok(())
     }
 
     pub async fn set_user_id(&self, user_id: Option<String>) {
@@ -217,6 +378,20 @@ impl TimberlogsClient {
         *self.config.session_id.lock().await = session_id;
     }
 
+    pub async fn trace(
+        &self,
+        message: impl Into<String>,
+        data: Option<std::collections::HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log(LogEntry {
+            level: LogLevel::Trace,
+            message: message.into(),
+            data,
+            ..Default::default()
+        })
+        .await
+    }
+
     pub async fn debug(
         &self,
         message: impl Into<String>,
@@ -273,12 +448,73 @@ impl TimberlogsClient {
         .await
     }
 
+    pub async fn fatal(
+        &self,
+        message: impl Into<String>,
+        data: Option<std::collections::HashMap<String, serde_json::Value>>,
+    ) -> Result<(), TimberlogsError> {
+        self.log(LogEntry {
+            level: LogLevel::Fatal,
+            message: message.into(),
+            data,
+            ..Default::default()
+        })
+        .await
+    }
+
     pub async fn log(&self, entry: LogEntry) -> Result<(), TimberlogsError> {
-        if entry.level < self.config.min_level {
+        self.check_open()?;
+        let target = entry.target.as_deref().unwrap_or(&self.config.source);
+        if entry.level < self.config.levels.lock().await.level_for(target) {
+            self.config.stats.filtered(1);
             return Ok(());
         }
+        let args = self.build_args(entry)?;
+        self.enqueue(args).await
+    }
 
-        validate_entry(&entry)?;
+    /// The part of [`log`](Self::log) before queueing, for callers without
+    /// a runtime: `Ok(None)` if the level filter skips `entry`. Blocks on the
+    /// level lock, so it must not be called from async code.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn prepare_blocking(
+        &self,
+        entry: LogEntry,
+    ) -> Result<Option<CreateLogArgs>, TimberlogsError> {
+        self.check_open()?;
+        let target = entry.target.as_deref().unwrap_or(&self.config.source);
+        if entry.level < self.config.levels.blocking_lock().level_for(target) {
+            self.config.stats.filtered(1);
+            return Ok(None);
+        }
+        self.build_args(entry).map(Some)
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn levels_blocking(&self) -> MutexGuard<'_, LevelFilter> {
+        self.config.levels.blocking_lock()
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn set_user_id_blocking(&self, user_id: Option<String>) {
+        *self.config.user_id.blocking_lock() = user_id;
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn set_session_id_blocking(&self, session_id: Option<String>) {
+        *self.config.session_id.blocking_lock() = session_id;
+    }
+
+    /// Fills in the client's defaults and validates the result.
+    fn build_args(&self, mut entry: LogEntry) -> Result<CreateLogArgs, TimberlogsError> {
+        if entry.trace_id.is_none() {
+            if let Some(trace) = TraceContext::current() {
+                entry.trace_id = Some(trace.trace_id);
+                entry.span_id = Some(trace.span_id);
+                entry.parent_span_id = trace.parent_span_id;
+                entry.trace_flags = Some(trace.trace_flags);
+            }
+        }
 
         let user_id = entry
             .user_id
@@ -288,10 +524,11 @@ impl TimberlogsClient {
             .or_else(|| self.config.session_id.try_lock().ok()?.clone());
 
         let args = CreateLogArgs {
+            id: Some(entry.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string())),
             level: entry.level,
             message: entry.message,
             source: self.config.source.clone(),
-            environment: self.config.environment,
+            environment: self.config.environment.clone(),
             version: self.config.version.clone(),
             user_id,
             session_id,
@@ -306,44 +543,53 @@ impl TimberlogsClient {
             timestamp: entry.timestamp,
             ip_address: entry.ip_address,
             country: entry.country,
+            trace_id: entry.trace_id,
+            span_id: entry.span_id,
+            parent_span_id: entry.parent_span_id,
+            trace_flags: entry.trace_flags,
+            suppressed_count: None,
         };
+        if let Err(e) = args.validate() {
+            self.config.stats.validation_failures(1);
+            return Err(e);
+        }
+        Ok(args)
+    }
 
+    /// Queues `args` through the sampler, flushing once a batch is full.
+    pub(crate) async fn enqueue(&self, args: CreateLogArgs) -> Result<(), TimberlogsError> {
         let should_flush = {
             let mut inner = self.inner.lock().await;
-            inner.queue.push(args);
-            inner.queue.len() >= self.config.batch_size
+            // Checked again under the lock so nothing lands after shutdown took the queue.
+            self.check_open()?;
+            let ClientInner { queue, sampler, .. } = &mut *inner;
+            let before = queue.len();
+            match sampler {
+                // Flow steps bypass sampling so step indices stay contiguous.
+                Some(sampler) if args.flow_id.is_none() => {
+                    if !sampler.admit(args, queue) {
+                        self.config.stats.dropped(1);
+                    }
+                }
+                _ => queue.push(args),
+            }
+            self.config.stats.enqueued((queue.len() - before) as u64);
+            self.config.stats.queue_depth(queue.len());
+            queue.len() >= self.config.batch_size
         };
 
         if should_flush {
-            flush_batch(&self.config, &self.inner).await?;
+            flush_batch(&self.config, &self.inner, false).await?;
         }
 
         Ok(())
     }
 
     pub async fn flow(&self, name: impl Into<String>) -> Result<Flow<'_>, TimberlogsError> {
+        self.check_open()?;
         let name = name.into();
-        let http = {
-            let inner = self.inner.lock().await;
-            inner.http.clone()
-        };
-
-        let flows_url = format!("{}{}", self.config.base_url, FLOWS_PATH);
-        let response = http
-            .post(&flows_url)
-            .header("Content-Type", "application/json")
-            .header("X-API-Key", &self.config.api_key)
-            .json(&serde_json::json!({ "name": name }))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let body = response.text().await.unwrap_or_default();
-            return Err(TimberlogsError::Http { status, body });
-        }
-
-        let data: FlowResponse = response.json().await?;
+        let transport = &self.config.transport;
+        let data = attempt(&self.config, transport.create_flow(&name)).await?;
         Ok(Flow {
             id: data.flow_id,
             name: data.name,
@@ -358,90 +604,186 @@ impl TimberlogsClient {
         format: RawFormat,
         options: Option<IngestRawOptions>,
     ) -> Result<(), TimberlogsError> {
+        self.check_open()?;
         let body = body.into();
-        let opts = options.unwrap_or_default();
-
-        let logs_url = format!("{}{}", self.config.base_url, LOGS_PATH);
-        let mut url = format!("{}?format={}", logs_url, format.as_str());
-        if let Some(ref source) = opts.source {
-            url.push_str(&format!("&source={}", urlencoding::encode(source)));
-        }
-        if let Some(env) = opts.environment {
-            let env_str = serde_json::to_value(env)
-                .ok()
-                .and_then(|v| v.as_str().map(String::from))
-                .unwrap_or_default();
-            url.push_str(&format!("&environment={}", urlencoding::encode(&env_str)));
-        }
-        if let Some(level) = opts.level {
-            let level_str = serde_json::to_value(level)
-                .ok()
-                .and_then(|v| v.as_str().map(String::from))
-                .unwrap_or_default();
-            url.push_str(&format!("&level={}", urlencoding::encode(&level_str)));
-        }
-        if let Some(ref dataset) = opts.dataset {
-            url.push_str(&format!("&dataset={}", urlencoding::encode(dataset)));
-        }
-
-        let http = {
-            let inner = self.inner.lock().await;
-            inner.http.clone()
+        let mut opts = options.unwrap_or_default();
+        if let Some(ref env) = opts.environment {
+            env.validate()?;
+        }
+        if self.config.legacy_levels {
+            opts.level = opts.level.map(|level| level.legacy());
+        }
+
+        let transport = &self.config.transport;
+        with_retry(&self.config, || transport.ingest_raw(&body, format, &opts)).await
+    }
+
+    /// Sends the entries of a dead-letter file again, in order and bypassing
+    /// the queue, and returns how many were accepted. Entries rejected again
+    /// go to the dead-letter sink. On any other error nothing is written
+    /// anywhere, so the whole file can be resubmitted later.
+    pub async fn resubmit_dead_letters(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<usize, TimberlogsError> {
+        self.check_open()?;
+        let logs: Vec<CreateLogArgs> = read_dead_letters(path)?
+            .into_iter()
+            .map(|letter| letter.log)
+            .collect();
+        let (progress, result) = send_batch(&self.config, &logs).await;
+        result.map(|()| progress.sent)
+    }
+
+    /// Asks the ingest API whether it is reachable and accepts the API key,
+    /// without retries or the circuit breaker, so it answers quickly. Meant
+    /// for startup and readiness checks.
+    pub async fn verify(&self) -> Result<VerifyReport, TimberlogsError> {
+        self.config.transport.verify().await
+    }
+
+    pub async fn stats(&self) -> Stats {
+        let queue_depth = self.inner.lock().await.queue.len();
+        self.config.stats.snapshot(queue_depth)
+    }
+
+    /// Always `Closed` when no circuit breaker is configured.
+    pub fn circuit_state(&self) -> CircuitState {
+        self.config
+            .breaker
+            .as_ref()
+            .map_or(CircuitState::Closed, CircuitBreaker::state)
+    }
+
+    pub async fn flush(&self) -> Result<(), TimberlogsError> {
+        flush_batch(&self.config, &self.inner, true).await
+    }
+
+    pub async fn disconnect(&mut self) -> Result<(), TimberlogsError> {
+        {
+            // Let an in-flight background flush finish before stopping the task.
+            let _flushing = self.config.flush_lock.lock().await;
+            self.stop_tasks();
+        }
+        self.flush().await
+    }
+
+    /// Stops accepting entries and sends whatever is queued, retrying until
+    /// `timeout` runs out. Entries still unsent at the deadline are dropped.
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        let deadline = Instant::now() + timeout;
+        let config = &*self.config;
+        config.closed.store(true, Ordering::SeqCst);
+
+        let mut report = ShutdownReport::default();
+        let flushing = with_deadline(config, deadline, config.flush_lock.lock()).await;
+        // Stopped on every path, so nothing is sent after the report.
+        self.stop_tasks();
+
+        let logs = {
+            let mut guard = self.inner.lock().await;
+            let ClientInner { queue, sampler } = &mut *guard;
+            if let Some(sampler) = sampler {
+                let summaries = sampler.drain_suppressed(true, queue);
+                config.stats.enqueued(summaries as u64);
+            }
+            config.stats.queue_depth(0);
+            std::mem::take(queue)
         };
+        if flushing.is_none() {
+            // A batch is still in flight; it is not counted either way.
+            report.dropped = logs.len();
+            config.stats.dropped(logs.len() as u64);
+            return report;
+        }
+        if logs.is_empty() {
+            return report;
+        }
 
-        let retry = &self.config.retry;
+        // A send cut off by the deadline counts as dropped, even if part of
+        // it was delivered. Dead-lettered entries count as dropped too.
+        let mut remaining = &logs[..];
         let mut last_error = None;
-        let mut delay = retry.initial_delay_ms;
-
-        for attempt in 0..=retry.max_retries {
-            let result = http
-                .post(&url)
-                .header("Content-Type", format.content_type())
-                .header("X-API-Key", &self.config.api_key)
-                .body(body.clone())
-                .send()
-                .await;
-
-            match result {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        return Ok(());
-                    }
-                    let status = response.status().as_u16();
-                    let resp_body = response.text().await.unwrap_or_default();
-                    last_error = Some(TimberlogsError::Http { status, body: resp_body });
-                }
-                Err(e) => {
-                    last_error = Some(TimberlogsError::Request(e));
-                }
+        while let Some((progress, result)) =
+            with_deadline(config, deadline, send_batch(config, remaining)).await
+        {
+            report.sent += progress.sent;
+            remaining = &remaining[progress.handled()..];
+            let Err(e) = result else {
+                break;
+            };
+            config.report_error(&e);
+            last_error = Some(e);
+            let backoff = config
+                .executor
+                .sleep(Duration::from_millis(config.retry.initial_delay_ms));
+            if with_deadline(config, deadline, backoff).await.is_none() {
+                break;
             }
-
-            if attempt < retry.max_retries {
-                tokio::time::sleep(Duration::from_millis(delay)).await;
-                delay = (delay * 2).min(retry.max_delay_ms);
+        }
+        if config.dead_letter.is_some() && !remaining.is_empty() {
+            let e = last_error
+                .unwrap_or_else(|| std::io::Error::from(std::io::ErrorKind::TimedOut).into());
+            for log in remaining {
+                config.dead_letter(log, &e);
             }
+        } else {
+            config.stats.dropped(remaining.len() as u64);
         }
-
-        Err(last_error.unwrap())
+        report.dropped = logs.len() - report.sent;
+        report
     }
 
-    pub async fn flush(&self) -> Result<(), TimberlogsError> {
-        flush_batch(&self.config, &self.inner).await
+    fn check_open(&self) -> Result<(), TimberlogsError> {
+        if self.config.closed.load(Ordering::SeqCst) {
+            return Err(TimberlogsError::NotConnected);
+        }
+        Ok(())
     }
 
-    pub async fn disconnect(&mut self) -> Result<(), TimberlogsError> {
-        if let Some(handle) = self.flush_handle.take() {
+    fn stop_tasks(&self) {
+        if let Some(ref handle) = self.flush_handle {
+            handle.abort();
+        }
+        if let Some(ref handle) = self.reload_handle {
             handle.abort();
         }
-        self.flush().await
     }
 }
 
 impl Drop for TimberlogsClient {
     fn drop(&mut self) {
-        if let Some(handle) = self.flush_handle.take() {
+        if let Some(handle) = self.reload_handle.take() {
             handle.abort();
         }
+        let flush_handle = self.flush_handle.take();
+        if !self.config.flush_on_drop || self.config.closed.load(Ordering::SeqCst) {
+            if let Some(handle) = flush_handle {
+                handle.abort();
+            }
+            return;
+        }
+
+        // Best effort: this only runs if the executor outlives the client.
+        if !self.config.executor.can_spawn() {
+            if let Some(handle) = flush_handle {
+                handle.abort();
+            }
+            let queued = self.inner.try_lock().map_or(0, |inner| inner.queue.len());
+            self.config.stats.dropped(queued as u64);
+            return;
+        }
+        let config = Arc::clone(&self.config);
+        let inner = Arc::clone(&self.inner);
+        self.config.executor.spawn(Box::pin(async move {
+            let _flushing = config.flush_lock.lock().await;
+            if let Some(handle) = flush_handle {
+                handle.abort();
+            }
+            if let Err(e) = send_queued(&config, &inner, true).await {
+                config.report_error(&e);
+            }
+        }));
     }
 }
 
@@ -457,12 +799,22 @@ impl<'a> Flow<'a> {
         self.step_index
     }
 
+    pub async fn trace(
+        &mut self,
+        message: impl Into<String>,
+        data: Option<std::collections::HashMap<String, serde_json::Value>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        self.log_with_level(LogLevel::Trace, message, data, None)
+            .await
+    }
+
     pub async fn debug(
         &mut self,
         message: impl Into<String>,
         data: Option<std::collections::HashMap<String, serde_json::Value>>,
     ) -> Result<&mut Self, TimberlogsError> {
-        self.log_with_level(LogLevel::Debug, message, data, None).await
+        self.log_with_level(LogLevel::Debug, message, data, None)
+            .await
     }
 
     pub async fn info(
@@ -470,7 +822,8 @@ impl<'a> Flow<'a> {
         message: impl Into<String>,
         data: Option<std::collections::HashMap<String, serde_json::Value>>,
     ) -> Result<&mut Self, TimberlogsError> {
-        self.log_with_level(LogLevel::Info, message, data, None).await
+        self.log_with_level(LogLevel::Info, message, data, None)
+            .await
     }
 
     pub async fn warn(
@@ -478,7 +831,8 @@ impl<'a> Flow<'a> {
         message: impl Into<String>,
         data: Option<std::collections::HashMap<String, serde_json::Value>>,
     ) -> Result<&mut Self, TimberlogsError> {
-        self.log_with_level(LogLevel::Warn, message, data, None).await
+        self.log_with_level(LogLevel::Warn, message, data, None)
+            .await
     }
 
     pub async fn error(
@@ -486,7 +840,17 @@ impl<'a> Flow<'a> {
         message: impl Into<String>,
         data: Option<std::collections::HashMap<String, serde_json::Value>>,
     ) -> Result<&mut Self, TimberlogsError> {
-        self.log_with_level(LogLevel::Error, message, data, None).await
+        self.log_with_level(LogLevel::Error, message, data, None)
+            .await
+    }
+
+    pub async fn fatal(
+        &mut self,
+        message: impl Into<String>,
+        data: Option<std::collections::HashMap<String, serde_json::Value>>,
+    ) -> Result<&mut Self, TimberlogsError> {
+        self.log_with_level(LogLevel::Fatal, message, data, None)
+            .await
     }
 
     pub async fn log_with_level(
@@ -496,7 +860,7 @@ impl<'a> Flow<'a> {
         data: Option<std::collections::HashMap<String, serde_json::Value>>,
         tags: Option<Vec<String>>,
     ) -> Result<&mut Self, TimberlogsError> {
-        if level < self.client.config.min_level {
+        if !self.client.check_level(level).await {
             return Ok(self);
         }
 
@@ -519,83 +883,319 @@ impl<'a> Flow<'a> {
     }
 }
 
+fn load_level_file(
+    config: &ClientConfig,
+    level_file: &LevelFileConfig,
+) -> Result<(), TimberlogsError> {
+    let directives = LevelDirectives::load(&level_file.path)?;
+    config
+        .levels
+        .try_lock()
+        .map_err(|_| TimberlogsError::Validation("level filter is busy".into()))?
+        .apply(directives);
+    Ok(())
+}
+
+async fn watch_level_file(config: Arc<ClientConfig>) {
+    let Some(ref level_file) = config.level_file else {
+        return;
+    };
+    let modified = |path: &std::path::Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    let mut last_modified = modified(&level_file.path);
+    let poll_interval = level_file.poll_interval_ms.map(Duration::from_millis);
+    // Signal handling needs a tokio reactor, so other executors only poll.
+    #[cfg(all(unix, feature = "shutdown-signal"))]
+    let mut hangup = if level_file.reload_on_sighup && tokio::runtime::Handle::try_current().is_ok()
+    {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok()
+    } else {
+        None
+    };
+    #[cfg(not(all(unix, feature = "shutdown-signal")))]
+    let mut hangup: Option<()> = None;
+    if poll_interval.is_none() && hangup.is_none() {
+        return;
+    }
+
+    loop {
+        let tick = async {
+            match poll_interval {
+                Some(interval) => config.executor.sleep(interval).await,
+                None => std::future::pending().await,
+            }
+        };
+        let changed = match select(
+            std::pin::pin!(tick),
+            std::pin::pin!(wait_for_hangup(&mut hangup)),
+        )
+        .await
+        {
+            Either::Left(_) => {
+                let current = modified(&level_file.path);
+                let changed = current != last_modified;
+                last_modified = current;
+                changed
+            }
+            Either::Right(_) => true,
+        };
+
+        if changed {
+            let result = match LevelDirectives::load(&level_file.path) {
+                Ok(directives) => {
+                    config.levels.lock().await.apply(directives);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                config.report_error(&e);
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "shutdown-signal"))]
+async fn wait_for_hangup(hangup: &mut Option<tokio::signal::unix::Signal>) {
+    match hangup {
+        Some(signal) => {
+            signal.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(not(all(unix, feature = "shutdown-signal")))]
+async fn wait_for_hangup(_hangup: &mut Option<()>) {
+    std::future::pending().await
+}
+
 async fn flush_batch(
     config: &ClientConfig,
     inner: &Arc<Mutex<ClientInner>>,
+    drain_throttled: bool,
 ) -> Result<(), TimberlogsError> {
-    let (logs, http) = {
+    let _flushing = config.flush_lock.lock().await;
+    send_queued(config, inner, drain_throttled).await
+}
+
+/// Sends everything queued. On failure, whatever wasn't handled goes to the
+/// dead-letter sink if there is one, and back to the front of the queue
+/// otherwise or while the circuit is open. Callers must hold `flush_lock`.
+async fn send_queued(
+    config: &ClientConfig,
+    inner: &Arc<Mutex<ClientInner>>,
+    drain_throttled: bool,
+) -> Result<(), TimberlogsError> {
+    let logs = {
         let mut guard = inner.lock().await;
-        if guard.queue.is_empty() {
+        let ClientInner { queue, sampler } = &mut *guard;
+        if let Some(sampler) = sampler {
+            let summaries = sampler.drain_suppressed(drain_throttled, queue);
+            config.stats.enqueued(summaries as u64);
+        }
+        if queue.is_empty() {
             return Ok(());
         }
-        let logs = std::mem::take(&mut guard.queue);
-        let http = guard.http.clone();
-        (logs, http)
+        config.stats.queue_depth(0);
+        std::mem::take(queue)
     };
 
-    match send_batch(&http, &config.base_url, &config.api_key, &config.retry, &logs).await {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            let mut guard = inner.lock().await;
-            let mut requeued = logs;
-            requeued.append(&mut guard.queue);
-            guard.queue = requeued;
-            Err(e)
+    let (progress, result) = send_batch(config, &logs).await;
+    let Err(e) = result else {
+        return Ok(());
+    };
+    if config.dead_letter.is_some() && !matches!(e, TimberlogsError::CircuitOpen) {
+        for log in &logs[progress.handled()..] {
+            config.dead_letter(log, &e);
         }
+        return Err(e);
     }
+    let mut guard = inner.lock().await;
+    let mut requeued = logs;
+    requeued.drain(..progress.handled());
+    config.stats.requeued(requeued.len() as u64);
+    requeued.append(&mut guard.queue);
+    guard.queue = requeued;
+    config.stats.queue_depth(guard.queue.len());
+    Err(e)
 }
 
+/// How far [`send_batch`] got. Entries are handled in order, so the first
+/// `handled()` of them are done with.
+#[derive(Debug, Default)]
+struct Progress {
+    sent: usize,
+    rejected: usize,
+}
+
+impl Progress {
+    fn handled(&self) -> usize {
+        self.sent + self.rejected
+    }
+}
+
+/// Sends `logs` in order, in requests of at most `max_batch_bytes`. A request
+/// the server rejects is halved until the entries at fault are isolated and
+/// dead-lettered; so are entries rejected individually in a response. Stops
+/// at the first error that retrying might fix, and returns it.
 async fn send_batch(
-    http: &reqwest::Client,
-    base_url: &str,
-    api_key: &str,
-    retry: &RetryConfig,
+    config: &ClientConfig,
     logs: &[CreateLogArgs],
-) -> Result<(), TimberlogsError> {
-    let payload = BatchPayload {
-        logs: logs.to_vec(),
+) -> (Progress, Result<(), TimberlogsError>) {
+    let legacy;
+    let logs = if config.legacy_levels {
+        legacy = logs
+            .iter()
+            .cloned()
+            .map(CreateLogArgs::into_legacy_level)
+            .collect::<Vec<_>>();
+        &legacy[..]
+    } else {
+        logs
     };
 
-    let url = format!("{}{}", base_url, LOGS_PATH);
-    let mut last_error = None;
-    let mut delay = retry.initial_delay_ms;
-
-    for attempt in 0..=retry.max_retries {
-        let result = http
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("X-API-Key", api_key)
-            .json(&payload)
-            .send()
-            .await;
-
-        match result {
+    let transport = &config.transport;
+    let mut progress = Progress::default();
+    // Chunks are popped from the back, so this holds them in reverse order.
+    let mut pending = chunk_by_size(logs, config.max_batch_bytes);
+    pending.reverse();
+    while let Some(chunk) = pending.pop() {
+        let timed_send = || async {
+            let started = Instant::now();
+            let result = transport.send_batch(chunk).await;
+            config.stats.batch_latency(started.elapsed());
+            result
+        };
+        match with_retry(config, timed_send).await {
             Ok(response) => {
-                if response.status().is_success() {
-                    let _body: IngestResponse = response.json().await?;
-                    return Ok(());
+                let mut rejected = 0;
+                for entry in &response.rejected {
+                    if let Some(log) = chunk.get(entry.index) {
+                        config.dead_letter(log, &TimberlogsError::Validation(entry.error.clone()));
+                        rejected += 1;
+                    }
                 }
-                let status = response.status().as_u16();
-                let body = response.text().await.unwrap_or_default();
-                last_error = Some(TimberlogsError::Http { status, body });
+                config.stats.sent((chunk.len() - rejected) as u64);
+                progress.sent += chunk.len() - rejected;
+                progress.rejected += rejected;
             }
-            Err(e) => {
-                last_error = Some(TimberlogsError::Request(e));
+            Err(e) if is_rejection(&e) => {
+                if chunk.len() > 1 {
+                    let (front, back) = chunk.split_at(chunk.len() / 2);
+                    pending.push(back);
+                    pending.push(front);
+                } else {
+                    config.dead_letter(&chunk[0], &e);
+                    progress.rejected += 1;
+                }
             }
+            Err(e) => return (progress, Err(e)),
         }
+    }
+    (progress, Ok(()))
+}
+
+/// Splits `logs` into runs whose batch body fits in `max_bytes`. An entry
+/// that is larger on its own still goes out, alone.
+fn chunk_by_size(logs: &[CreateLogArgs], max_bytes: usize) -> Vec<&[CreateLogArgs]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut size = BATCH_ENVELOPE_BYTES;
+    for (i, log) in logs.iter().enumerate() {
+        // Plus one for the separating comma.
+        let len = serde_json::to_vec(log).map_or(0, |json| json.len()) + 1;
+        if i > start && size + len > max_bytes {
+            chunks.push(&logs[start..i]);
+            start = i;
+            size = BATCH_ENVELOPE_BYTES;
+        }
+        size += len;
+    }
+    if start < logs.len() {
+        chunks.push(&logs[start..]);
+    }
+    chunks
+}
 
-        if attempt < retry.max_retries {
-            tokio::time::sleep(Duration::from_millis(delay)).await;
-            delay = (delay * 2).min(retry.max_delay_ms);
+/// Whether the request was refused for what it contains, which sending it
+/// again won't change.
+fn is_rejection(error: &TimberlogsError) -> bool {
+    matches!(
+        error,
+        TimberlogsError::Validation(_)
+            | TimberlogsError::Http {
+                status: 400 | 413 | 422,
+                ..
+            }
+    )
+}
+
+/// Runs `future`, giving up with `None` once `deadline` passes.
+async fn with_deadline<F: Future>(
+    config: &ClientConfig,
+    deadline: Instant,
+    future: F,
+) -> Option<F::Output> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let future = std::pin::pin!(future);
+    match select(future, config.executor.sleep(remaining)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+/// Runs `op` until it succeeds, backing off exponentially between attempts.
+/// Errors that retrying can't fix are returned straight away, and so is
+/// `CircuitOpen` once the circuit breaker trips.
+async fn with_retry<T, F, Fut>(config: &ClientConfig, mut op: F) -> Result<T, TimberlogsError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, TimberlogsError>>,
+{
+    let retry = &config.retry;
+    let mut delay = retry.initial_delay_ms;
+    let mut attempts = 0;
+    loop {
+        match attempt(config, op()).await {
+            Ok(value) => return Ok(value),
+            Err(e @ TimberlogsError::CircuitOpen) => return Err(e),
+            Err(e) if is_rejection(&e) => return Err(e),
+            Err(e) if attempts >= retry.max_retries => return Err(e),
+            Err(_) => {}
         }
+        attempts += 1;
+        config.stats.retried(1);
+        config.executor.sleep(Duration::from_millis(delay)).await;
+        delay = (delay * 2).min(retry.max_delay_ms);
     }
+}
 
-    Err(last_error.unwrap())
+/// Makes a single request through the circuit breaker, if there is one.
+/// Rejections count as successes: the API answered, it just didn't like
+/// the content.
+async fn attempt<T>(
+    config: &ClientConfig,
+    request: impl Future<Output = Result<T, TimberlogsError>>,
+) -> Result<T, TimberlogsError> {
+    let Some(ref breaker) = config.breaker else {
+        return request.await;
+    };
+    if !breaker.try_acquire() {
+        return Err(TimberlogsError::CircuitOpen);
+    }
+    let result = request.await;
+    match result {
+        Err(ref e) if !is_rejection(e) => breaker.record_failure(),
+        _ => breaker.record_success(),
+    }
+    result
 }
 
 impl Default for LogEntry {
     fn default() -> Self {
         Self {
+            id: None,
             level: LogLevel::Info,
             message: String::new(),
             data: None,
@@ -611,6 +1211,11 @@ impl Default for LogEntry {
             timestamp: None,
             ip_address: None,
             country: None,
+            trace_id: None,
+            span_id: None,
+            parent_span_id: None,
+            trace_flags: None,
+            target: None,
         }
     }
 }
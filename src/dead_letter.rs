@@ -0,0 +1,120 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::TimberlogsError;
+use crate::types::CreateLogArgs;
+
+/// Receives logs that will never be delivered: entries the server rejected,
+/// and, once a sink is configured, entries whose send failed after all
+/// retries or that were still unsent when `shutdown` gave up.
+pub trait DeadLetterSink: Send + Sync {
+    fn dead_letter(
+        &self,
+        log: &CreateLogArgs,
+        error: &TimberlogsError,
+    ) -> Result<(), TimberlogsError>;
+}
+
+/// One line of a dead-letter file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    pub log: CreateLogArgs,
+    pub error: String,
+    /// Milliseconds since the Unix epoch.
+    pub failed_at: u64,
+}
+
+/// Appends each dead letter to a file as a line of JSON.
+///
+/// The file can be sent again later with
+/// [`TimberlogsClient::resubmit_dead_letters`](crate::TimberlogsClient::resubmit_dead_letters).
+pub struct JsonlDeadLetterSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl JsonlDeadLetterSink {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, TimberlogsError> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl DeadLetterSink for JsonlDeadLetterSink {
+    fn dead_letter(
+        &self,
+        log: &CreateLogArgs,
+        error: &TimberlogsError,
+    ) -> Result<(), TimberlogsError> {
+        let failed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let mut line = serde_json::to_string(&DeadLetter {
+            log: log.clone(),
+            error: error.to_string(),
+            failed_at,
+        })
+        .map_err(|e| TimberlogsError::Validation(e.to_string()))?;
+        line.push('\n');
+        // One write per line keeps lines whole if several clients share a file.
+        self.file.lock().unwrap().write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Hands each dead letter to a closure.
+pub struct CallbackDeadLetterSink<F>(F);
+
+impl<F> CallbackDeadLetterSink<F>
+where
+    F: Fn(&CreateLogArgs, &TimberlogsError) + Send + Sync,
+{
+    pub fn new(callback: F) -> Self {
+        Self(callback)
+    }
+}
+
+impl<F> DeadLetterSink for CallbackDeadLetterSink<F>
+where
+    F: Fn(&CreateLogArgs, &TimberlogsError) + Send + Sync,
+{
+    fn dead_letter(
+        &self,
+        log: &CreateLogArgs,
+        error: &TimberlogsError,
+    ) -> Result<(), TimberlogsError> {
+        (self.0)(log, error);
+        Ok(())
+    }
+}
+
+/// Reads a file written by [`JsonlDeadLetterSink`]. Blank lines are skipped.
+pub fn read_dead_letters(path: impl AsRef<Path>) -> Result<Vec<DeadLetter>, TimberlogsError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut letters = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let letter = serde_json::from_str(&line)
+            .map_err(|e| TimberlogsError::Validation(format!("line {}: {e}", i + 1)))?;
+        letters.push(letter);
+    }
+    Ok(letters)
+}
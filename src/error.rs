@@ -11,6 +11,12 @@ pub enum TimberlogsError {
     #[error("request failed: {0}")]
     Request(#[from] reqwest::Error),
 
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("circuit breaker is open")]
+    CircuitOpen,
+
     #[error("client is not connected")]
     NotConnected,
 }
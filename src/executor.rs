@@ -0,0 +1,131 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use futures_util::future::{AbortHandle, Abortable};
+
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+/// Spawns the client's background tasks and drives its timers.
+///
+/// [`TokioExecutor`] is used unless `TimberlogsConfig::executor` says
+/// otherwise. Tokio is still a dependency with other executors: the built-in
+/// HTTP transport uses reqwest, which needs a tokio reactor. Outside a tokio
+/// runtime, its requests run on a background thread named `timberlogs-http`
+/// that the crate starts on first use, with a current-thread tokio runtime.
+pub trait Executor: Send + Sync {
+    fn spawn(&self, future: BoxFuture<()>);
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<()>;
+
+    /// Whether `spawn` works from the current thread. A client dropped
+    /// where it doesn't skips its flush on drop.
+    fn can_spawn(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioExecutor;
+
+impl Executor for TokioExecutor {
+    fn spawn(&self, future: BoxFuture<()>) {
+        tokio::spawn(future);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    fn can_spawn(&self) -> bool {
+        tokio::runtime::Handle::try_current().is_ok()
+    }
+}
+
+#[cfg(feature = "async-std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStdExecutor;
+
+#[cfg(feature = "async-std")]
+impl Executor for AsyncStdExecutor {
+    fn spawn(&self, future: BoxFuture<()>) {
+        async_std::task::spawn(future);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
+
+#[cfg(feature = "smol")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmolExecutor;
+
+#[cfg(feature = "smol")]
+impl Executor for SmolExecutor {
+    fn spawn(&self, future: BoxFuture<()>) {
+        smol::spawn(future).detach();
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        Box::pin(async move {
+            smol::Timer::after(duration).await;
+        })
+    }
+}
+
+/// Awaits a future that needs a tokio reactor, such as a reqwest request.
+/// Outside a tokio runtime it runs on a single-threaded runtime started on
+/// first use, so the HTTP transports work whatever the executor.
+pub(crate) async fn on_tokio<F>(future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return future.await;
+    }
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    fallback_runtime().spawn(async move {
+        let _ = sender.send(future.await);
+    });
+    receiver.await.expect("timberlogs HTTP task panicked")
+}
+
+fn fallback_runtime() -> &'static tokio::runtime::Handle {
+    static HANDLE: OnceLock<tokio::runtime::Handle> = OnceLock::new();
+    HANDLE.get_or_init(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build timberlogs HTTP runtime");
+        let handle = runtime.handle().clone();
+        std::thread::Builder::new()
+            .name("timberlogs-http".into())
+            .spawn(move || runtime.block_on(std::future::pending::<()>()))
+            .expect("failed to spawn timberlogs HTTP thread");
+        handle
+    })
+}
+
+/// A background task that can be cancelled on any executor.
+pub(crate) struct TaskHandle(AbortHandle);
+
+impl TaskHandle {
+    pub(crate) fn spawn(
+        executor: &dyn Executor,
+        future: impl Future<Output = ()> + Send + 'static,
+    ) -> Self {
+        let (handle, registration) = AbortHandle::new_pair();
+        let task = Abortable::new(future, registration);
+        executor.spawn(Box::pin(async move {
+            let _ = task.await;
+        }));
+        Self(handle)
+    }
+
+    pub(crate) fn abort(&self) {
+        self.0.abort();
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::TimberlogsError;
+use crate::types::LogLevel;
+
+/// Where to load level overrides from and when to reload them.
+///
+/// The file uses `RUST_LOG`-style directives separated by commas or newlines:
+/// a bare level such as `info` sets the minimum level, and `prefix=level`
+/// overrides it for entries whose target (or the client source) starts with
+/// `prefix`. Lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct LevelFileConfig {
+    pub path: PathBuf,
+    pub poll_interval_ms: Option<u64>,
+    /// Reload on SIGHUP. Needs the `shutdown-signal` feature and a tokio
+    /// runtime; ignored otherwise.
+    pub reload_on_sighup: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct LevelDirectives {
+    pub min_level: Option<LogLevel>,
+    pub overrides: HashMap<String, LogLevel>,
+}
+
+impl LevelDirectives {
+    pub fn parse(input: &str) -> Result<Self, TimberlogsError> {
+        let mut directives = Self::default();
+        for directive in input
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(|line| line.split(','))
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+        {
+            match directive.split_once('=') {
+                Some((prefix, level)) => {
+                    directives
+                        .overrides
+                        .insert(prefix.trim().to_string(), level.parse()?);
+                }
+                None => directives.min_level = Some(directive.parse()?),
+            }
+        }
+        Ok(directives)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, TimberlogsError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            TimberlogsError::Validation(format!("failed to read {}: {e}", path.display()))
+        })?;
+        Self::parse(&contents)
+    }
+}
+
+/// The levels in effect: the configured ones, replaced by the level file on
+/// each reload, with changes made at runtime layered on top.
+pub(crate) struct LevelFilter {
+    base: LogLevel,
+    file: LevelDirectives,
+    runtime_min_level: Option<LogLevel>,
+    /// `None` removes an override from the layers below.
+    runtime_overrides: HashMap<String, Option<LogLevel>>,
+    min_level: LogLevel,
+    overrides: HashMap<String, LogLevel>,
+}
+
+impl LevelFilter {
+    pub fn new(min_level: LogLevel, overrides: HashMap<String, LogLevel>) -> Self {
+        Self {
+            base: min_level,
+            file: LevelDirectives {
+                min_level: None,
+                overrides: overrides.clone(),
+            },
+            runtime_min_level: None,
+            runtime_overrides: HashMap::new(),
+            min_level,
+            overrides,
+        }
+    }
+
+    pub fn min_level(&self) -> LogLevel {
+        self.min_level
+    }
+
+    pub fn set_min_level(&mut self, level: LogLevel) {
+        self.runtime_min_level = Some(level);
+        self.rebuild();
+    }
+
+    pub fn set_override(&mut self, prefix: String, level: Option<LogLevel>) {
+        self.runtime_overrides.insert(prefix, level);
+        self.rebuild();
+    }
+
+    /// Replaces the levels from the file with `directives`, falling back to
+    /// the configured minimum level when the directives do not set one.
+    /// Levels set at runtime still take precedence.
+    pub fn apply(&mut self, directives: LevelDirectives) {
+        self.file = directives;
+        self.rebuild();
+    }
+
+    fn rebuild(&mut self) {
+        self.min_level = self
+            .runtime_min_level
+            .or(self.file.min_level)
+            .unwrap_or(self.base);
+        self.overrides = self.file.overrides.clone();
+        for (prefix, level) in &self.runtime_overrides {
+            match level {
+                Some(level) => {
+                    self.overrides.insert(prefix.clone(), *level);
+                }
+                None => {
+                    self.overrides.remove(prefix);
+                }
+            }
+        }
+    }
+
+    /// Returns the minimum level for `target`, using the longest matching
+    /// override prefix.
+    pub fn level_for(&self, target: &str) -> LogLevel {
+        self.overrides
+            .iter()
+            .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.min_level)
+    }
+}
@@ -1,7 +1,58 @@
+mod agent;
+#[cfg(feature = "blocking")]
+mod blocking;
+mod breaker;
 mod client;
+mod dead_letter;
 mod error;
+mod executor;
+mod levels;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod query;
+mod sampling;
+mod signal;
+mod stats;
+mod tail;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod trace;
+mod transport;
 mod types;
 
-pub use client::{Flow, RetryConfig, TimberlogsClient, TimberlogsConfig};
+pub use agent::{FileAgent, FileAgentConfig, WatchedFile};
+#[cfg(feature = "blocking")]
+pub use blocking::{BlockingFlow, BlockingTimberlogsClient};
+pub use breaker::{CircuitBreakerConfig, CircuitState};
+pub use client::{Flow, RetryConfig, ShutdownReport, TimberlogsClient, TimberlogsConfig};
+pub use dead_letter::{
+    read_dead_letters, CallbackDeadLetterSink, DeadLetter, DeadLetterSink, JsonlDeadLetterSink,
+};
 pub use error::TimberlogsError;
-pub use types::{Environment, IngestRawOptions, LogEntry, LogLevel, RawFormat};
+#[cfg(feature = "async-std")]
+pub use executor::AsyncStdExecutor;
+#[cfg(feature = "smol")]
+pub use executor::SmolExecutor;
+pub use executor::{BoxFuture, Executor, TokioExecutor};
+pub use levels::LevelFileConfig;
+#[cfg(feature = "opentelemetry")]
+pub use otel::{TimberlogsLogRecord, TimberlogsLogger, TimberlogsLoggerProvider};
+#[cfg(feature = "otlp")]
+pub use otlp::{OtlpConfig, OtlpProtocol, OtlpTransport};
+pub use query::{
+    FlowDetails, FlowPage, FlowQuery, FlowSummary, LogPage, LogQuery, LogRecord, QueryClient,
+    QueryConfig,
+};
+pub use sampling::{RateLimit, SamplingConfig};
+pub use signal::shutdown_all;
+#[cfg(feature = "shutdown-signal")]
+pub use signal::{shutdown_on_signal, shutdown_signal};
+pub use stats::{LatencyHistogram, Stats, LATENCY_BUCKETS_MS};
+pub use trace::TraceContext;
+pub use transport::{idempotency_key, HttpTransport, InMemoryTransport, RawIngest, Transport};
+pub use types::{
+    ApiLimits, CreateLogArgs, Environment, FlowResponse, IngestRawOptions, IngestResponse,
+    LogEntry, LogLevel, RawFormat, RejectedEntry, VerifyReport, VerifyResponse,
+};
@@ -0,0 +1,281 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity};
+use opentelemetry::{InstrumentationScope, Key, SpanId, TraceFlags, TraceId};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::client::TimberlogsClient;
+use crate::error::TimberlogsError;
+use crate::types::{LogEntry, LogLevel};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+
+enum Message {
+    Entry(Box<LogEntry>),
+    Flush(oneshot::Sender<Result<(), TimberlogsError>>),
+}
+
+/// An OpenTelemetry [`LoggerProvider`] that forwards log records to a
+/// [`TimberlogsClient`].
+///
+/// Records are handed to a background task in emission order, spawned on the
+/// client's executor. Up to 10,000 records wait for it; records emitted while
+/// that many are waiting are counted as dropped in the client's stats. Use it
+/// with any OpenTelemetry log appender, such as `opentelemetry-appender-log`
+/// or `opentelemetry-appender-tracing`.
+#[derive(Clone)]
+pub struct TimberlogsLoggerProvider {
+    sender: mpsc::Sender<Message>,
+    client: Arc<TimberlogsClient>,
+}
+
+impl TimberlogsLoggerProvider {
+    pub fn new(client: Arc<TimberlogsClient>) -> Self {
+        Self::with_capacity(client, DEFAULT_CAPACITY)
+    }
+
+    /// Like [`new`](Self::new), with room for `capacity` records waiting to
+    /// be logged. A `capacity` of 0 is treated as 1.
+    pub fn with_capacity(client: Arc<TimberlogsClient>, capacity: usize) -> Self {
+        let (sender, mut receiver) = mpsc::channel(capacity.max(1));
+        let executor = client.executor();
+        let logging = Arc::clone(&client);
+        executor.spawn(Box::pin(async move {
+            let client = logging;
+            while let Some(message) = receiver.recv().await {
+                match message {
+                    Message::Entry(entry) => {
+                        if let Err(e) = client.log(*entry).await {
+                            client.report_error(&e);
+                        }
+                    }
+                    Message::Flush(done) => {
+                        let _ = done.send(client.flush().await);
+                    }
+                }
+            }
+        }));
+        Self { sender, client }
+    }
+
+    /// Waits until every record emitted so far has been logged, then flushes
+    /// the client.
+    pub async fn force_flush(&self) -> Result<(), TimberlogsError> {
+        let (done, wait) = oneshot::channel();
+        self.sender
+            .send(Message::Flush(done))
+            .await
+            .map_err(|_| TimberlogsError::NotConnected)?;
+        wait.await.map_err(|_| TimberlogsError::NotConnected)?
+    }
+}
+
+impl LoggerProvider for TimberlogsLoggerProvider {
+    type Logger = TimberlogsLogger;
+
+    fn logger_with_scope(&self, scope: InstrumentationScope) -> Self::Logger {
+        TimberlogsLogger {
+            scope_name: Cow::Owned(scope.name().to_string()),
+            sender: self.sender.clone(),
+            client: Arc::clone(&self.client),
+        }
+    }
+}
+
+pub struct TimberlogsLogger {
+    scope_name: Cow<'static, str>,
+    sender: mpsc::Sender<Message>,
+    client: Arc<TimberlogsClient>,
+}
+
+impl Logger for TimberlogsLogger {
+    type LogRecord = TimberlogsLogRecord;
+
+    fn create_log_record(&self) -> Self::LogRecord {
+        TimberlogsLogRecord::default()
+    }
+
+    fn emit(&self, record: Self::LogRecord) {
+        let entry = record.into_entry(&self.scope_name);
+        if let Err(mpsc::error::TrySendError::Full(_)) =
+            self.sender.try_send(Message::Entry(Box::new(entry)))
+        {
+            self.client.record_dropped(1);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TimberlogsLogRecord {
+    event_name: Option<&'static str>,
+    target: Option<Cow<'static, str>>,
+    timestamp: Option<SystemTime>,
+    observed_timestamp: Option<SystemTime>,
+    severity_text: Option<&'static str>,
+    severity_number: Option<Severity>,
+    body: Option<AnyValue>,
+    attributes: Vec<(Key, AnyValue)>,
+    trace: Option<(TraceId, SpanId, Option<TraceFlags>)>,
+}
+
+impl LogRecord for TimberlogsLogRecord {
+    fn set_event_name(&mut self, name: &'static str) {
+        self.event_name = Some(name);
+    }
+
+    fn set_target<T>(&mut self, target: T)
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        self.target = Some(target.into());
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = Some(timestamp);
+    }
+
+    fn set_observed_timestamp(&mut self, timestamp: SystemTime) {
+        self.observed_timestamp = Some(timestamp);
+    }
+
+    fn set_severity_text(&mut self, text: &'static str) {
+        self.severity_text = Some(text);
+    }
+
+    fn set_severity_number(&mut self, number: Severity) {
+        self.severity_number = Some(number);
+    }
+
+    fn set_body(&mut self, body: AnyValue) {
+        self.body = Some(body);
+    }
+
+    fn add_attributes<I, K, V>(&mut self, attributes: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<Key>,
+        V: Into<AnyValue>,
+    {
+        self.attributes
+            .extend(attributes.into_iter().map(|(k, v)| (k.into(), v.into())));
+    }
+
+    fn add_attribute<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Key>,
+        V: Into<AnyValue>,
+    {
+        self.attributes.push((key.into(), value.into()));
+    }
+
+    fn set_trace_context(
+        &mut self,
+        trace_id: TraceId,
+        span_id: SpanId,
+        trace_flags: Option<TraceFlags>,
+    ) {
+        self.trace = Some((trace_id, span_id, trace_flags));
+    }
+}
+
+impl TimberlogsLogRecord {
+    fn level(&self) -> LogLevel {
+        if let Some(number) = self.severity_number {
+            return match number as i32 {
+                ..=4 => LogLevel::Trace,
+                5..=8 => LogLevel::Debug,
+                9..=12 => LogLevel::Info,
+                13..=16 => LogLevel::Warn,
+                17..=20 => LogLevel::Error,
+                _ => LogLevel::Fatal,
+            };
+        }
+        self.severity_text
+            .and_then(|text| text.parse().ok())
+            .unwrap_or(LogLevel::Info)
+    }
+
+    /// Maps the record onto a [`LogEntry`]. Well-known semantic convention
+    /// attributes fill the matching entry fields; the rest go into `data`.
+    fn into_entry(self, scope_name: &str) -> LogEntry {
+        let level = self.level();
+        let message = match self.body {
+            Some(AnyValue::String(s)) => s.to_string(),
+            Some(other) => any_value_to_json(other).to_string(),
+            None => self.event_name.unwrap_or(scope_name).to_string(),
+        };
+
+        let mut entry = LogEntry {
+            level,
+            message,
+            target: Some(
+                self.target
+                    .map(Cow::into_owned)
+                    .unwrap_or_else(|| scope_name.to_string()),
+            ),
+            timestamp: self
+                .timestamp
+                .or(self.observed_timestamp)
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64),
+            ..Default::default()
+        };
+
+        if let Some((trace_id, span_id, flags)) = self.trace {
+            entry.trace_id = Some(trace_id.to_string());
+            entry.span_id = Some(span_id.to_string());
+            entry.trace_flags = flags.map(TraceFlags::to_u8);
+        }
+
+        let mut data = HashMap::new();
+        if let Some(name) = self.event_name {
+            data.insert("event.name".to_string(), name.into());
+        }
+        for (key, value) in self.attributes {
+            let text = || match &value {
+                AnyValue::String(s) => s.to_string(),
+                other => any_value_to_json(other.clone()).to_string(),
+            };
+            match key.as_str() {
+                "exception.type" => entry.error_name = Some(text()),
+                "exception.stacktrace" => entry.error_stack = Some(text()),
+                "enduser.id" | "user.id" => entry.user_id = Some(text()),
+                "session.id" => entry.session_id = Some(text()),
+                "http.request.id" | "request.id" => entry.request_id = Some(text()),
+                "client.address" => entry.ip_address = Some(text()),
+                _ => {
+                    data.insert(key.as_str().to_string(), any_value_to_json(value));
+                }
+            }
+        }
+        if !data.is_empty() {
+            entry.data = Some(data);
+        }
+
+        entry
+    }
+}
+
+fn any_value_to_json(value: AnyValue) -> serde_json::Value {
+    match value {
+        AnyValue::Int(i) => i.into(),
+        AnyValue::Double(f) => f.into(),
+        AnyValue::String(s) => s.to_string().into(),
+        AnyValue::Boolean(b) => b.into(),
+        AnyValue::Bytes(bytes) => bytes
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>()
+            .into(),
+        AnyValue::ListAny(values) => values.into_iter().map(any_value_to_json).collect(),
+        AnyValue::Map(map) => map
+            .into_iter()
+            .map(|(k, v)| (k.as_str().to_string(), any_value_to_json(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        _ => serde_json::Value::Null,
+    }
+}
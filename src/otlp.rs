@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+
+use crate::error::TimberlogsError;
+use crate::transport::{error_for_status, send, Transport};
+use crate::types::{
+    CreateLogArgs, FlowResponse, IngestRawOptions, IngestResponse, LogLevel, RawFormat,
+};
+
+const SCOPE_NAME: &str = "timberlogs";
+
+/// Body encoding for OTLP/HTTP export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OtlpProtocol {
+    #[default]
+    Json,
+    Protobuf,
+}
+
+impl OtlpProtocol {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OtlpProtocol::Json => "application/json",
+            OtlpProtocol::Protobuf => "application/x-protobuf",
+        }
+    }
+}
+
+/// Sends log batches to an OpenTelemetry collector instead of Timberlogs.
+///
+/// `endpoint` is the collector's base URL (e.g. `http://localhost:4318`);
+/// batches are posted to `{endpoint}/v1/logs`. Flows get locally generated ids
+/// and `ingest_raw` is unavailable in this mode.
+#[derive(Debug, Clone, Default)]
+pub struct OtlpConfig {
+    pub endpoint: String,
+    pub protocol: OtlpProtocol,
+    pub headers: HashMap<String, String>,
+}
+
+impl OtlpConfig {
+    fn logs_url(&self) -> String {
+        format!("{}/v1/logs", self.endpoint.trim_end_matches('/'))
+    }
+
+    fn encode(&self, logs: &[CreateLogArgs]) -> Vec<u8> {
+        let request = export_request(logs);
+        match self.protocol {
+            OtlpProtocol::Json => serde_json::to_vec(&request.to_json()).unwrap_or_default(),
+            OtlpProtocol::Protobuf => prost::Message::encode_to_vec(&request),
+        }
+    }
+}
+
+/// The transport used when [`OtlpConfig`] is set on the client.
+pub struct OtlpTransport {
+    http: reqwest::Client,
+    config: OtlpConfig,
+}
+
+impl OtlpTransport {
+    pub fn new(config: OtlpConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for OtlpTransport {
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError> {
+        let request = self
+            .config
+            .headers
+            .iter()
+            .fold(
+                self.http.post(self.config.logs_url()),
+                |req, (name, value)| req.header(name, value),
+            )
+            .header("Content-Type", self.config.protocol.content_type())
+            .body(self.config.encode(logs));
+        let response = send(request).await?;
+        error_for_status(response).await?;
+        Ok(IngestResponse::accepted(logs.len()))
+    }
+
+    async fn ingest_raw(
+        &self,
+        _body: &str,
+        _format: RawFormat,
+        _options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError> {
+        Err(TimberlogsError::Validation(
+            "ingest_raw is not supported when exporting to OTLP".into(),
+        ))
+    }
+
+    // Collectors have no flow endpoint, so flows are named locally.
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError> {
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+        Ok(FlowResponse {
+            flow_id: format!("{name}-{}", &suffix[..8]),
+            name: name.to_string(),
+        })
+    }
+}
+
+// Subset of opentelemetry/proto/collector/logs/v1 and friends.
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ExportLogsServiceRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub resource_logs: Vec<ResourceLogs>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ResourceLogs {
+    #[prost(message, optional, tag = "1")]
+    pub resource: Option<Resource>,
+    #[prost(message, repeated, tag = "2")]
+    pub scope_logs: Vec<ScopeLogs>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct Resource {
+    #[prost(message, repeated, tag = "1")]
+    pub attributes: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ScopeLogs {
+    #[prost(message, optional, tag = "1")]
+    pub scope: Option<InstrumentationScope>,
+    #[prost(message, repeated, tag = "2")]
+    pub log_records: Vec<LogRecord>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct InstrumentationScope {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct LogRecord {
+    #[prost(fixed64, tag = "1")]
+    pub time_unix_nano: u64,
+    #[prost(int32, tag = "2")]
+    pub severity_number: i32,
+    #[prost(string, tag = "3")]
+    pub severity_text: String,
+    #[prost(message, optional, tag = "5")]
+    pub body: Option<AnyValue>,
+    #[prost(message, repeated, tag = "6")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(fixed32, tag = "8")]
+    pub flags: u32,
+    #[prost(bytes = "vec", tag = "9")]
+    pub trace_id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "10")]
+    pub span_id: Vec<u8>,
+    #[prost(fixed64, tag = "11")]
+    pub observed_time_unix_nano: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct KeyValue {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<AnyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct AnyValue {
+    #[prost(oneof = "Value", tags = "1, 2, 3, 4, 5, 6")]
+    pub value: Option<Value>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub(crate) enum Value {
+    #[prost(string, tag = "1")]
+    String(String),
+    #[prost(bool, tag = "2")]
+    Bool(bool),
+    #[prost(int64, tag = "3")]
+    Int(i64),
+    #[prost(double, tag = "4")]
+    Double(f64),
+    #[prost(message, tag = "5")]
+    Array(ArrayValue),
+    #[prost(message, tag = "6")]
+    Kvlist(KeyValueList),
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ArrayValue {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<AnyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct KeyValueList {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<KeyValue>,
+}
+
+fn severity_number(level: LogLevel) -> i32 {
+    match level {
+        LogLevel::Trace => 1,
+        LogLevel::Debug => 5,
+        LogLevel::Info => 9,
+        LogLevel::Warn => 13,
+        LogLevel::Error => 17,
+        LogLevel::Fatal => 21,
+    }
+}
+
+fn string_value(value: impl Into<String>) -> AnyValue {
+    AnyValue {
+        value: Some(Value::String(value.into())),
+    }
+}
+
+fn key_value(key: &str, value: AnyValue) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(value),
+    }
+}
+
+fn json_to_any_value(value: &serde_json::Value) -> AnyValue {
+    let value = match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::Bool(b) => Some(Value::Bool(*b)),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Some(Value::Int(i)),
+            None => Some(Value::Double(n.as_f64().unwrap_or_default())),
+        },
+        serde_json::Value::String(s) => Some(Value::String(s.clone())),
+        serde_json::Value::Array(values) => Some(Value::Array(ArrayValue {
+            values: values.iter().map(json_to_any_value).collect(),
+        })),
+        serde_json::Value::Object(map) => Some(Value::Kvlist(KeyValueList {
+            values: map
+                .iter()
+                .map(|(k, v)| key_value(k, json_to_any_value(v)))
+                .collect(),
+        })),
+    };
+    AnyValue { value }
+}
+
+fn hex_bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .filter_map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn log_record(log: &CreateLogArgs, now_nanos: u64) -> LogRecord {
+    let mut attributes = Vec::new();
+    let mut push_str = |key: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            attributes.push(key_value(key, string_value(value.as_str())));
+        }
+    };
+    push_str("enduser.id", &log.user_id);
+    push_str("session.id", &log.session_id);
+    push_str("http.request.id", &log.request_id);
+    push_str("exception.type", &log.error_name);
+    push_str("exception.stacktrace", &log.error_stack);
+    push_str("timberlogs.flow_id", &log.flow_id);
+    push_str("timberlogs.dataset", &log.dataset);
+    push_str("client.address", &log.ip_address);
+    push_str("timberlogs.country", &log.country);
+    if let Some(ref tags) = log.tags {
+        attributes.push(key_value(
+            "timberlogs.tags",
+            AnyValue {
+                value: Some(Value::Array(ArrayValue {
+                    values: tags.iter().map(|t| string_value(t.as_str())).collect(),
+                })),
+            },
+        ));
+    }
+    if let Some(step) = log.step_index {
+        attributes.push(key_value(
+            "timberlogs.step_index",
+            AnyValue {
+                value: Some(Value::Int(step.into())),
+            },
+        ));
+    }
+    if let Some(count) = log.suppressed_count {
+        attributes.push(key_value(
+            "timberlogs.suppressed_count",
+            AnyValue {
+                value: Some(Value::Int(count.into())),
+            },
+        ));
+    }
+    if let Some(ref data) = log.data {
+        let mut keys: Vec<_> = data.keys().collect();
+        keys.sort();
+        for key in keys {
+            attributes.push(key_value(key, json_to_any_value(&data[key])));
+        }
+    }
+
+    LogRecord {
+        time_unix_nano: log
+            .timestamp
+            .map(|ms| ms.saturating_mul(1_000_000))
+            .unwrap_or(now_nanos),
+        observed_time_unix_nano: now_nanos,
+        severity_number: severity_number(log.level),
+        severity_text: log.level.as_str().to_ascii_uppercase(),
+        body: Some(string_value(log.message.as_str())),
+        attributes,
+        flags: log.trace_flags.map(u32::from).unwrap_or_default(),
+        trace_id: log.trace_id.as_deref().map(hex_bytes).unwrap_or_default(),
+        span_id: log.span_id.as_deref().map(hex_bytes).unwrap_or_default(),
+    }
+}
+
+pub(crate) fn export_request(logs: &[CreateLogArgs]) -> ExportLogsServiceRequest {
+    let now_nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+
+    // One ResourceLogs per distinct source/environment/version, in first-seen order.
+    type ResourceKey<'a> = (&'a str, &'a str, Option<&'a str>);
+    let mut resources: Vec<(ResourceKey, Vec<LogRecord>)> = Vec::new();
+    for log in logs {
+        let key = (
+            log.source.as_str(),
+            log.environment.as_str(),
+            log.version.as_deref(),
+        );
+        let record = log_record(log, now_nanos);
+        match resources.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, records)) => records.push(record),
+            None => resources.push((key, vec![record])),
+        }
+    }
+
+    ExportLogsServiceRequest {
+        resource_logs: resources
+            .into_iter()
+            .map(|((source, environment, version), log_records)| {
+                let mut attributes = vec![
+                    key_value("service.name", string_value(source)),
+                    key_value("deployment.environment.name", string_value(environment)),
+                ];
+                if let Some(version) = version {
+                    attributes.push(key_value("service.version", string_value(version)));
+                }
+                ResourceLogs {
+                    resource: Some(Resource { attributes }),
+                    scope_logs: vec![ScopeLogs {
+                        scope: Some(InstrumentationScope {
+                            name: SCOPE_NAME.to_string(),
+                            version: env!("CARGO_PKG_VERSION").to_string(),
+                        }),
+                        log_records,
+                    }],
+                }
+            })
+            .collect(),
+    }
+}
+
+// OTLP/JSON differs from the default protobuf JSON mapping: ids are hex
+// strings, 64-bit integers are strings and enums are numbers.
+
+impl ExportLogsServiceRequest {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "resourceLogs": self.resource_logs.iter().map(|rl| serde_json::json!({
+                "resource": {
+                    "attributes": rl.resource.as_ref().map(|r| attributes_json(&r.attributes)),
+                },
+                "scopeLogs": rl.scope_logs.iter().map(|sl| serde_json::json!({
+                    "scope": sl.scope.as_ref().map(|s| serde_json::json!({
+                        "name": s.name,
+                        "version": s.version,
+                    })),
+                    "logRecords": sl.log_records.iter().map(LogRecord::to_json).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl LogRecord {
+    fn to_json(&self) -> serde_json::Value {
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let mut record = serde_json::json!({
+            "timeUnixNano": self.time_unix_nano.to_string(),
+            "observedTimeUnixNano": self.observed_time_unix_nano.to_string(),
+            "severityNumber": self.severity_number,
+            "severityText": self.severity_text,
+            "body": self.body.as_ref().map(any_value_json),
+            "attributes": attributes_json(&self.attributes),
+        });
+        if !self.trace_id.is_empty() {
+            record["traceId"] = hex(&self.trace_id).into();
+        }
+        if !self.span_id.is_empty() {
+            record["spanId"] = hex(&self.span_id).into();
+        }
+        if self.flags != 0 {
+            record["flags"] = self.flags.into();
+        }
+        record
+    }
+}
+
+fn attributes_json(attributes: &[KeyValue]) -> serde_json::Value {
+    attributes
+        .iter()
+        .map(|kv| {
+            serde_json::json!({
+                "key": kv.key,
+                "value": kv.value.as_ref().map(any_value_json),
+            })
+        })
+        .collect()
+}
+
+fn any_value_json(value: &AnyValue) -> serde_json::Value {
+    match &value.value {
+        None => serde_json::json!({}),
+        Some(Value::String(s)) => serde_json::json!({ "stringValue": s }),
+        Some(Value::Bool(b)) => serde_json::json!({ "boolValue": b }),
+        Some(Value::Int(i)) => serde_json::json!({ "intValue": i.to_string() }),
+        Some(Value::Double(d)) => serde_json::json!({ "doubleValue": d }),
+        Some(Value::Array(a)) => serde_json::json!({
+            "arrayValue": { "values": a.values.iter().map(any_value_json).collect::<Vec<_>>() },
+        }),
+        Some(Value::Kvlist(kv)) => serde_json::json!({
+            "kvlistValue": { "values": attributes_json(&kv.values) },
+        }),
+    }
+}
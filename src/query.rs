@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures_util::stream::{self, Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::client::DEFAULT_BASE_URL;
+use crate::error::TimberlogsError;
+use crate::executor::{Executor, TokioExecutor};
+use crate::tail;
+use crate::transport::{error_for_status, send, FLOWS_PATH, LOGS_PATH, TAIL_PATH};
+use crate::types::{CreateLogArgs, Environment, LogLevel};
+
+/// A log as stored by Timberlogs, with the id and timestamp the server
+/// assigned when the entry didn't carry its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub level: LogLevel,
+    pub message: String,
+    pub source: String,
+    pub environment: Environment,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<HashMap<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_stack: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_index: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dataset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_flags: Option<u8>,
+    /// Set on throttling summaries: the number of occurrences represented by
+    /// this entry, including itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppressed_count: Option<u32>,
+}
+
+impl LogRecord {
+    /// Stores `log` the way the ingest API does: a missing id gets a fresh
+    /// one and a missing timestamp becomes `received_at`.
+    pub fn from_log(log: CreateLogArgs, received_at: u64) -> Self {
+        Self {
+            id: log.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            timestamp: log.timestamp.unwrap_or(received_at),
+            level: log.level,
+            message: log.message,
+            source: log.source,
+            environment: log.environment,
+            version: log.version,
+            user_id: log.user_id,
+            session_id: log.session_id,
+            request_id: log.request_id,
+            data: log.data,
+            error_name: log.error_name,
+            error_stack: log.error_stack,
+            tags: log.tags.unwrap_or_default(),
+            flow_id: log.flow_id,
+            step_index: log.step_index,
+            dataset: log.dataset,
+            ip_address: log.ip_address,
+            country: log.country,
+            trace_id: log.trace_id,
+            span_id: log.span_id,
+            parent_span_id: log.parent_span_id,
+            trace_flags: log.trace_flags,
+            suppressed_count: log.suppressed_count,
+        }
+    }
+}
+
+/// Filters for [`QueryClient::query`]. Every filter that is set must match;
+/// unset filters match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogQuery {
+    /// Any of these levels. Empty means all levels.
+    pub levels: Vec<LogLevel>,
+    pub source: Option<String>,
+    pub environment: Option<Environment>,
+    pub dataset: Option<String>,
+    pub user_id: Option<String>,
+    pub session_id: Option<String>,
+    pub flow_id: Option<String>,
+    /// Logs carrying all of these tags.
+    pub tags: Vec<String>,
+    /// Inclusive lower bound, in milliseconds since the Unix epoch.
+    pub from: Option<u64>,
+    /// Exclusive upper bound, in milliseconds since the Unix epoch.
+    pub to: Option<u64>,
+    /// Case-insensitive text to find in the message or error fields.
+    pub search: Option<String>,
+    /// Page size. The API defaults to 100 and allows at most 1000.
+    pub limit: Option<u32>,
+    /// `next_cursor` of the previous page. For [`QueryClient::tail`], the
+    /// `id` of the last log seen instead.
+    pub cursor: Option<String>,
+}
+
+impl LogQuery {
+    /// Query string parameters for `GET /v1/logs`. Lists are comma-separated.
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if !self.levels.is_empty() {
+            let levels: Vec<&str> = self.levels.iter().map(LogLevel::as_str).collect();
+            params.push(("level", levels.join(",")));
+        }
+        let strings = [
+            ("source", &self.source),
+            ("dataset", &self.dataset),
+            ("userId", &self.user_id),
+            ("sessionId", &self.session_id),
+            ("flowId", &self.flow_id),
+            ("q", &self.search),
+            ("cursor", &self.cursor),
+        ];
+        for (name, value) in strings {
+            if let Some(value) = value {
+                params.push((name, value.clone()));
+            }
+        }
+        if let Some(ref env) = self.environment {
+            params.push(("environment", env.as_str().to_string()));
+        }
+        if !self.tags.is_empty() {
+            params.push(("tags", self.tags.join(",")));
+        }
+        if let Some(from) = self.from {
+            params.push(("from", from.to_string()));
+        }
+        if let Some(to) = self.to {
+            params.push(("to", to.to_string()));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        params
+    }
+}
+
+/// One page of query results, newest first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    pub logs: Vec<LogRecord>,
+    /// Pass as [`LogQuery::cursor`] to get the next page. `None` on the
+    /// last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// A flow as listed by [`QueryClient::list_flows`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowSummary {
+    pub flow_id: String,
+    pub name: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: u64,
+    pub step_count: u32,
+}
+
+/// A flow and its steps, from [`QueryClient::get_flow`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowDetails {
+    pub flow_id: String,
+    pub name: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: u64,
+    /// Logged steps, ordered by `step_index`.
+    pub steps: Vec<LogRecord>,
+}
+
+/// Filters for [`QueryClient::list_flows`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlowQuery {
+    /// Exact flow name, as passed to `TimberlogsClient::flow`.
+    pub name: Option<String>,
+    /// Inclusive lower bound on `created_at`.
+    pub from: Option<u64>,
+    /// Exclusive upper bound on `created_at`.
+    pub to: Option<u64>,
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+impl FlowQuery {
+    /// Query string parameters for `GET /v1/flows`.
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(ref name) = self.name {
+            params.push(("name", name.clone()));
+        }
+        if let Some(from) = self.from {
+            params.push(("from", from.to_string()));
+        }
+        if let Some(to) = self.to {
+            params.push(("to", to.to_string()));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(ref cursor) = self.cursor {
+            params.push(("cursor", cursor.clone()));
+        }
+        params
+    }
+}
+
+/// One page of flows, newest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowPage {
+    pub flows: Vec<FlowSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Default)]
+pub struct QueryConfig {
+    pub api_key: String,
+    pub base_url: Option<String>,
+    /// Times `tail`'s reconnect backoff. Defaults to [`TokioExecutor`].
+    pub executor: Option<Arc<dyn Executor>>,
+}
+
+/// Reads logs and flows back from Timberlogs.
+///
+/// Separate from [`TimberlogsClient`](crate::TimberlogsClient) since reading
+/// needs no queue or background task, and is often done with a different key.
+pub struct QueryClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    executor: Arc<dyn Executor>,
+}
+
+impl QueryClient {
+    /// Returns [`TimberlogsError::Validation`] if `api_key` is empty.
+    pub fn new(config: QueryConfig) -> Result<Self, TimberlogsError> {
+        if config.api_key.is_empty() {
+            return Err(TimberlogsError::Validation(
+                "api_key must not be empty".into(),
+            ));
+        }
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base_url: config
+                .base_url
+                .unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            api_key: config.api_key,
+            executor: config.executor.unwrap_or_else(|| Arc::new(TokioExecutor)),
+        })
+    }
+
+    async fn get(
+        &self,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<reqwest::Response, TimberlogsError> {
+        let request = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .header("X-API-Key", &self.api_key)
+            .query(params);
+        error_for_status(send(request).await?).await
+    }
+
+    /// Fetches one page of logs matching `query`.
+    pub async fn query(&self, query: &LogQuery) -> Result<LogPage, TimberlogsError> {
+        Ok(self
+            .get(LOGS_PATH, &query.to_params())
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Fetches a flow with its steps ordered by `step_index`, or `None` if
+    /// there is no flow with that id.
+    pub async fn get_flow(&self, flow_id: &str) -> Result<Option<FlowDetails>, TimberlogsError> {
+        let path = format!("{}/{}", FLOWS_PATH, urlencoding::encode(flow_id));
+        let mut flow: FlowDetails = match self.get(&path, &[]).await {
+            Ok(response) => response.json().await?,
+            Err(TimberlogsError::Http { status: 404, .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        flow.steps.sort_by_key(|step| step.step_index);
+        Ok(Some(flow))
+    }
+
+    /// Fetches one page of flows matching `query`, newest first.
+    pub async fn list_flows(&self, query: &FlowQuery) -> Result<FlowPage, TimberlogsError> {
+        Ok(self
+            .get(FLOWS_PATH, &query.to_params())
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Follows logs matching `query` as they arrive, over server-sent events.
+    ///
+    /// Starts with logs stored after `query.cursor`, which is a log `id`, or
+    /// with new logs when it is unset. Dropped connections are reopened with
+    /// backoff and resume after the last log delivered. The stream only ends
+    /// with an error the server won't recover from, such as a rejected key or
+    /// an invalid filter; `limit` is ignored.
+    pub fn tail(
+        &self,
+        query: LogQuery,
+    ) -> impl Stream<Item = Result<LogRecord, TimberlogsError>> + '_ {
+        tail::tail(self, query)
+    }
+
+    pub(crate) async fn open_tail(
+        &self,
+        query: &LogQuery,
+        last_id: Option<&str>,
+    ) -> Result<reqwest::Response, TimberlogsError> {
+        let mut params = query.to_params();
+        params.retain(|(name, _)| *name != "limit");
+        let mut request = self
+            .http
+            .get(format!("{}{}", self.base_url, TAIL_PATH))
+            .header("X-API-Key", &self.api_key)
+            .header("Accept", "text/event-stream")
+            .query(&params);
+        if let Some(id) = last_id {
+            request = request.header("Last-Event-ID", id);
+        }
+        error_for_status(send(request).await?).await
+    }
+
+    pub(crate) fn executor(&self) -> &dyn Executor {
+        &*self.executor
+    }
+
+    /// Every log matching `query`, following cursors until the last page.
+    /// Pages are only fetched as the stream is polled.
+    pub fn query_all(
+        &self,
+        query: LogQuery,
+    ) -> impl Stream<Item = Result<LogRecord, TimberlogsError>> + '_ {
+        stream::try_unfold(Some(query), move |next| async move {
+            let Some(mut query) = next else {
+                return Ok::<_, TimberlogsError>(None);
+            };
+            let page = self.query(&query).await?;
+            let next = page.next_cursor.map(|cursor| {
+                query.cursor = Some(cursor);
+                query
+            });
+            Ok(Some((stream::iter(page.logs.into_iter().map(Ok)), next)))
+        })
+        .try_flatten()
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::error::TimberlogsError;
+use crate::types::{CreateLogArgs, LogLevel};
+
+/// Token bucket settings for a single level: `burst` entries may be sent at
+/// once, refilling at `per_second` entries per second. Both must be greater
+/// than 0, and `per_second` finite.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+/// Volume controls applied to each entry before it is queued.
+///
+/// Levels missing from `rates` are always kept and levels missing from
+/// `rate_limits` are never limited. When `throttle_window_ms` is set, repeats
+/// of the same level and message within the window are collapsed into a single
+/// follow-up entry whose `suppressed_count` is the number of occurrences it
+/// represents, including itself. Messages that differ only in
+/// numbers or hex ids, such as `"retry 3 for 0x1f2e"` and `"retry 4 for
+/// 0x2a3b"`, count as repeats.
+#[derive(Debug, Clone, Default)]
+pub struct SamplingConfig {
+    pub rates: HashMap<LogLevel, f64>,
+    pub rate_limits: HashMap<LogLevel, RateLimit>,
+    pub throttle_window_ms: Option<u64>,
+}
+
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.burst),
+            updated: now,
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limit.per_second).min(f64::from(self.limit.burst));
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct ThrottleWindow {
+    started: Instant,
+    suppressed: u32,
+    last: Option<CreateLogArgs>,
+}
+
+pub(crate) struct Sampler {
+    rates: HashMap<LogLevel, f64>,
+    buckets: HashMap<LogLevel, TokenBucket>,
+    throttle_window: Option<Duration>,
+    windows: HashMap<(LogLevel, String), ThrottleWindow>,
+}
+
+impl Sampler {
+    pub fn new(config: SamplingConfig) -> Result<Self, TimberlogsError> {
+        for (level, rate) in &config.rates {
+            if !(0.0..=1.0).contains(rate) {
+                return Err(TimberlogsError::Validation(format!(
+                    "sample rate for {level:?} must be between 0 and 1"
+                )));
+            }
+        }
+        for (level, limit) in &config.rate_limits {
+            if !limit.per_second.is_finite() || limit.per_second <= 0.0 {
+                return Err(TimberlogsError::Validation(format!(
+                    "rate limit for {level:?} must allow a finite number above 0 per second"
+                )));
+            }
+            if limit.burst == 0 {
+                return Err(TimberlogsError::Validation(format!(
+                    "rate limit burst for {level:?} must be greater than 0"
+                )));
+            }
+        }
+
+        let now = Instant::now();
+        Ok(Self {
+            rates: config.rates,
+            buckets: config
+                .rate_limits
+                .into_iter()
+                .map(|(level, limit)| (level, TokenBucket::new(limit, now)))
+                .collect(),
+            throttle_window: config.throttle_window_ms.map(Duration::from_millis),
+            windows: HashMap::new(),
+        })
+    }
+
+    /// Pushes `args` onto `queue` if it survives sampling, throttling and rate
+    /// limiting. A summary of a previously throttled window may be pushed first.
+    /// Returns `false` if sampling or rate limiting dropped `args`; throttled
+    /// entries are still counted in their window's summary.
+    pub fn admit(&mut self, args: CreateLogArgs, queue: &mut Vec<CreateLogArgs>) -> bool {
+        let now = Instant::now();
+
+        if let Some(&rate) = self.rates.get(&args.level) {
+            if rate < 1.0 && rand::random::<f64>() >= rate {
+                return false;
+            }
+        }
+
+        if let Some(window) = self.throttle_window {
+            let key = (args.level, template(&args.message));
+            match self.windows.get_mut(&key) {
+                Some(state) if now.duration_since(state.started) < window => {
+                    state.suppressed += 1;
+                    state.last = Some(args);
+                    return true;
+                }
+                Some(state) => {
+                    if let Some(summary) = take_summary(state) {
+                        queue.push(summary);
+                    }
+                    state.started = now;
+                }
+                None => {
+                    self.windows.insert(
+                        key,
+                        ThrottleWindow {
+                            started: now,
+                            suppressed: 0,
+                            last: None,
+                        },
+                    );
+                }
+            }
+        }
+
+        if let Some(bucket) = self.buckets.get_mut(&args.level) {
+            if !bucket.try_take(now) {
+                return false;
+            }
+        }
+
+        queue.push(args);
+        true
+    }
+
+    /// Emits summaries for throttle windows that have closed, or for every
+    /// window when `force` is set, and forgets those windows. Returns how many
+    /// summaries were pushed.
+    pub fn drain_suppressed(&mut self, force: bool, queue: &mut Vec<CreateLogArgs>) -> usize {
+        let Some(window) = self.throttle_window else {
+            return 0;
+        };
+        let now = Instant::now();
+        let before = queue.len();
+        self.windows.retain(|_, state| {
+            if !force && now.duration_since(state.started) < window {
+                return true;
+            }
+            if let Some(summary) = take_summary(state) {
+                queue.push(summary);
+            }
+            false
+        });
+        queue.len() - before
+    }
+}
+
+/// `message` with each number or hex id replaced by `#`, so that messages
+/// built from the same format string share a throttle window.
+fn template(message: &str) -> String {
+    let mut template = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric()) {
+        template.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        let digits = word.strip_prefix("0x").unwrap_or(word);
+        if word.bytes().any(|b| b.is_ascii_digit()) && digits.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            template.push('#');
+        } else {
+            let mut in_number = false;
+            for c in word.chars() {
+                if c.is_ascii_digit() {
+                    if !in_number {
+                        template.push('#');
+                    }
+                    in_number = true;
+                } else {
+                    template.push(c);
+                    in_number = false;
+                }
+            }
+        }
+        rest = &rest[end..];
+    }
+    template.push_str(rest);
+    template
+}
+
+fn take_summary(state: &mut ThrottleWindow) -> Option<CreateLogArgs> {
+    let suppressed = std::mem::take(&mut state.suppressed);
+    let mut summary = state.last.take()?;
+    summary.suppressed_count = Some(suppressed);
+    Some(summary)
+}
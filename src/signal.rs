@@ -0,0 +1,62 @@
+#[cfg(feature = "shutdown-signal")]
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::join_all;
+
+use crate::client::{ShutdownReport, TimberlogsClient};
+
+/// Resolves when the process receives SIGTERM or SIGINT (Ctrl-C elsewhere).
+///
+/// Pass it to `axum::serve(..).with_graceful_shutdown` or tonic's
+/// `serve_with_shutdown`, then call [`shutdown_all`] once the server has
+/// drained. The handlers are installed on first poll and need a tokio runtime.
+#[cfg(feature = "shutdown-signal")]
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).ok();
+        let mut interrupt = signal(SignalKind::interrupt()).ok();
+        futures_util::future::select(
+            std::pin::pin!(recv(&mut terminate)),
+            std::pin::pin!(recv(&mut interrupt)),
+        )
+        .await;
+    }
+    #[cfg(not(unix))]
+    {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "shutdown-signal"))]
+async fn recv(signal: &mut Option<tokio::signal::unix::Signal>) {
+    match signal {
+        Some(signal) => {
+            signal.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Shuts the clients down concurrently, all within the same `timeout`.
+/// Reports are returned in the order the clients were given.
+pub async fn shutdown_all(clients: &[&TimberlogsClient], timeout: Duration) -> Vec<ShutdownReport> {
+    join_all(clients.iter().map(|client| client.shutdown(timeout))).await
+}
+
+/// Waits for [`shutdown_signal`], then shuts the clients down within
+/// `timeout`. Meant to be spawned next to the rest of the application.
+#[cfg(feature = "shutdown-signal")]
+pub async fn shutdown_on_signal(
+    clients: Vec<Arc<TimberlogsClient>>,
+    timeout: Duration,
+) -> Vec<ShutdownReport> {
+    shutdown_signal().await;
+    let clients: Vec<&TimberlogsClient> = clients.iter().map(|client| &**client).collect();
+    shutdown_all(&clients, timeout).await
+}
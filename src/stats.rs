@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the batch latency buckets, in milliseconds. A final
+/// bucket catches everything slower.
+pub const LATENCY_BUCKETS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// Distribution of how long single batch requests took, retries included
+/// as separate requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// Requests per bucket of [`LATENCY_BUCKETS_MS`], plus one for slower
+    /// requests. Not cumulative.
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum_ms: u64,
+}
+
+/// A snapshot of what the client has done since it was created.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Entries added to the queue, including throttling summaries.
+    pub enqueued: u64,
+    /// Entries below the minimum level for their target.
+    pub filtered: u64,
+    /// Entries refused by client-side validation.
+    pub validation_failures: u64,
+    /// Entries the server accepted.
+    pub sent: u64,
+    /// Requests of any kind that were retried after a failure.
+    pub retried: u64,
+    /// Entries given up on: sampled out, rate limited, rejected by the
+    /// server, dead-lettered, or unsent at the end of `shutdown`.
+    pub dropped: u64,
+    /// Entries put back in the queue after a failed flush.
+    pub requeued: u64,
+    pub queue_depth: usize,
+    pub batch_latency: LatencyHistogram,
+}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    source: String,
+    enqueued: AtomicU64,
+    filtered: AtomicU64,
+    validation_failures: AtomicU64,
+    sent: AtomicU64,
+    retried: AtomicU64,
+    dropped: AtomicU64,
+    requeued: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+    latency_count: AtomicU64,
+    latency_sum_ms: AtomicU64,
+}
+
+// With the `metrics` feature, each counter is also reported to the global
+// `metrics` recorder under `timberlogs_<name>_total`, labelled by source.
+macro_rules! counter_methods {
+    ($($name:ident),* $(,)?) => {
+        $(
+            pub fn $name(&self, n: u64) {
+                self.$name.fetch_add(n, Ordering::Relaxed);
+                #[cfg(feature = "metrics")]
+                metrics::counter!(
+                    concat!("timberlogs_", stringify!($name), "_total"),
+                    "source" => self.source.clone()
+                )
+                .increment(n);
+            }
+        )*
+    };
+}
+
+impl Counters {
+    pub fn new(source: String) -> Self {
+        Self {
+            source,
+            ..Default::default()
+        }
+    }
+
+    counter_methods!(
+        enqueued,
+        filtered,
+        validation_failures,
+        sent,
+        retried,
+        dropped,
+        requeued
+    );
+
+    pub fn batch_latency(&self, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&bound| ms <= bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_ms.fetch_add(ms, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("timberlogs_batch_duration_seconds", "source" => self.source.clone())
+            .record(elapsed.as_secs_f64());
+    }
+
+    /// Only feeds the `metrics` gauge; snapshots read the queue itself.
+    pub fn queue_depth(&self, _depth: usize) {
+        #[cfg(feature = "metrics")]
+        metrics::gauge!("timberlogs_queue_depth", "source" => self.source.clone())
+            .set(_depth as f64);
+    }
+
+    pub fn snapshot(&self, queue_depth: usize) -> Stats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        Stats {
+            enqueued: load(&self.enqueued),
+            filtered: load(&self.filtered),
+            validation_failures: load(&self.validation_failures),
+            sent: load(&self.sent),
+            retried: load(&self.retried),
+            dropped: load(&self.dropped),
+            requeued: load(&self.requeued),
+            queue_depth,
+            batch_latency: LatencyHistogram {
+                buckets: self.latency_buckets.iter().map(load).collect(),
+                count: load(&self.latency_count),
+                sum_ms: load(&self.latency_sum_ms),
+            },
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use futures_util::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+
+use crate::error::TimberlogsError;
+use crate::query::{LogQuery, LogRecord, QueryClient};
+
+const RECONNECT_INITIAL_MS: u64 = 500;
+const RECONNECT_MAX_MS: u64 = 30_000;
+
+struct Tail<'a> {
+    client: &'a QueryClient,
+    query: LogQuery,
+    // Id of the last record delivered, sent as `Last-Event-ID` on reconnect.
+    last_id: Option<String>,
+    body: Option<BoxStream<'static, Result<Vec<u8>, reqwest::Error>>>,
+    parser: EventParser,
+    delay: Duration,
+    done: bool,
+}
+
+pub(crate) fn tail(
+    client: &QueryClient,
+    mut query: LogQuery,
+) -> impl Stream<Item = Result<LogRecord, TimberlogsError>> + '_ {
+    let state = Tail {
+        client,
+        last_id: query.cursor.take(),
+        query,
+        body: None,
+        parser: EventParser::default(),
+        delay: Duration::from_millis(RECONNECT_INITIAL_MS),
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        let item = state.next().await?;
+        Some((item, state))
+    })
+}
+
+impl Tail<'_> {
+    async fn next(&mut self) -> Option<Result<LogRecord, TimberlogsError>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let Some(body) = self.body.as_mut() else {
+                match self
+                    .client
+                    .open_tail(&self.query, self.last_id.as_deref())
+                    .await
+                {
+                    Ok(response) => {
+                        self.body = Some(response.bytes_stream().map_ok(|b| b.to_vec()).boxed());
+                        self.parser = EventParser::default();
+                    }
+                    Err(e) if is_permanent(&e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                    Err(_) => self.backoff().await,
+                }
+                continue;
+            };
+
+            if let Some(event) = self.parser.next_event() {
+                self.delay = Duration::from_millis(RECONNECT_INITIAL_MS);
+                let record: Result<LogRecord, _> = serde_json::from_str(&event.data)
+                    .map_err(|e| TimberlogsError::Validation(format!("invalid tail event: {e}")));
+                // Servers that omit `id:` lines still resume after the last record.
+                match (event.id, &record) {
+                    (Some(id), _) => self.last_id = Some(id),
+                    (None, Ok(record)) => self.last_id = Some(record.id.clone()),
+                    (None, Err(_)) => {}
+                }
+                return Some(record);
+            }
+            match body.next().await {
+                Some(Ok(chunk)) => self.parser.push(&chunk),
+                // The server closed the stream or the connection dropped.
+                Some(Err(_)) | None => {
+                    self.body = None;
+                    self.backoff().await;
+                }
+            }
+        }
+    }
+
+    async fn backoff(&mut self) {
+        self.client.executor().sleep(self.delay).await;
+        self.delay = (self.delay * 2).min(Duration::from_millis(RECONNECT_MAX_MS));
+    }
+}
+
+// Bad keys, filters and cursors won't fix themselves by reconnecting.
+fn is_permanent(e: &TimberlogsError) -> bool {
+    match e {
+        TimberlogsError::Http { status, .. } => {
+            (400..500).contains(status) && *status != 408 && *status != 429
+        }
+        TimberlogsError::Validation(_) => true,
+        _ => false,
+    }
+}
+
+struct Event {
+    id: Option<String>,
+    data: String,
+}
+
+/// Splits a `text/event-stream` body into events. Only `id` and `data`
+/// fields are used; comments such as keep-alives are skipped.
+#[derive(Default)]
+struct EventParser {
+    buffer: Vec<u8>,
+    id: Option<String>,
+    data: Vec<String>,
+}
+
+impl EventParser {
+    fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    fn next_event(&mut self) -> Option<Event> {
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                let id = self.id.take();
+                if !self.data.is_empty() {
+                    let data = std::mem::take(&mut self.data).join("\n");
+                    return Some(Event { id, data });
+                }
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "id" => self.id = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                _ => {}
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::client::TimberlogsConfig;
+use crate::transport::{InMemoryTransport, Transport};
+use crate::types::{CreateLogArgs, Environment, LogLevel};
+
+/// Captures everything a client logs and offers assertions over it.
+///
+/// ```ignore
+/// let captured = CapturedLogs::new();
+/// let client = TimberlogsClient::new(captured.config());
+/// handler(&client).await;
+/// captured.assert_logged(LogLevel::Warn, "cache miss");
+/// captured.assert_no_unexpected_errors();
+/// ```
+///
+/// Logs are only visible once the client has sent them; [`CapturedLogs::config`]
+/// uses a batch size of 1 so that happens on every call.
+#[derive(Clone, Default)]
+pub struct CapturedLogs {
+    transport: Arc<InMemoryTransport>,
+    expected_errors: Arc<Mutex<Vec<String>>>,
+}
+
+impl CapturedLogs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A config that sends every log straight to this capture.
+    pub fn config(&self) -> TimberlogsConfig {
+        TimberlogsConfig {
+            source: "test".into(),
+            environment: Environment::Development,
+            batch_size: Some(1),
+            transport: Some(self.transport()),
+            ..Default::default()
+        }
+    }
+
+    pub fn transport(&self) -> Arc<dyn Transport> {
+        self.transport.clone()
+    }
+
+    pub fn logs(&self) -> Vec<CreateLogArgs> {
+        self.transport.logs()
+    }
+
+    pub fn clear(&self) {
+        self.transport.clear();
+    }
+
+    pub fn with_level(&self, level: LogLevel) -> Vec<CreateLogArgs> {
+        self.filter(|log| log.level == level)
+    }
+
+    /// Logs for the flow, ordered by step index.
+    pub fn for_flow(&self, flow_id: &str) -> Vec<CreateLogArgs> {
+        let mut logs = self.filter(|log| log.flow_id.as_deref() == Some(flow_id));
+        logs.sort_by_key(|log| log.step_index);
+        logs
+    }
+
+    pub fn with_tag(&self, tag: &str) -> Vec<CreateLogArgs> {
+        self.filter(|log| log.tags.iter().flatten().any(|t| t == tag))
+    }
+
+    pub fn with_data_key(&self, key: &str) -> Vec<CreateLogArgs> {
+        self.filter(|log| log.data.as_ref().is_some_and(|data| data.contains_key(key)))
+    }
+
+    pub fn filter(&self, predicate: impl Fn(&CreateLogArgs) -> bool) -> Vec<CreateLogArgs> {
+        self.logs()
+            .into_iter()
+            .filter(|log| predicate(log))
+            .collect()
+    }
+
+    /// Panics unless a log at `level` contains `message_contains`.
+    #[track_caller]
+    pub fn assert_logged(&self, level: LogLevel, message_contains: &str) {
+        let found = self
+            .logs()
+            .iter()
+            .any(|log| log.level == level && log.message.contains(message_contains));
+        assert!(
+            found,
+            "expected a {level} log containing {message_contains:?}, captured:\n{}",
+            self.snapshot()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_not_logged(&self, level: LogLevel, message_contains: &str) {
+        let found = self
+            .logs()
+            .iter()
+            .any(|log| log.level == level && log.message.contains(message_contains));
+        assert!(
+            !found,
+            "expected no {level} log containing {message_contains:?}, captured:\n{}",
+            self.snapshot()
+        );
+    }
+
+    /// Allows `Error` and `Fatal` logs containing `message_contains` in
+    /// [`CapturedLogs::assert_no_unexpected_errors`].
+    pub fn expect_error(&self, message_contains: impl Into<String>) {
+        self.expected_errors
+            .lock()
+            .unwrap()
+            .push(message_contains.into());
+    }
+
+    /// Panics if any `Error` or `Fatal` log wasn't declared with
+    /// [`CapturedLogs::expect_error`].
+    #[track_caller]
+    pub fn assert_no_unexpected_errors(&self) {
+        let expected = self.expected_errors.lock().unwrap().clone();
+        let unexpected: Vec<_> = self
+            .filter(|log| {
+                log.level >= LogLevel::Error
+                    && !expected.iter().any(|e| log.message.contains(e.as_str()))
+            })
+            .iter()
+            .map(snapshot_line)
+            .collect();
+        assert!(
+            unexpected.is_empty(),
+            "unexpected error logs:\n{}",
+            unexpected.join("\n")
+        );
+    }
+
+    /// One line per log with its level, message, flow, tags and data. Fields
+    /// that change between runs, such as timestamps and trace ids, are left out
+    /// so the output can be compared against a stored snapshot.
+    pub fn snapshot(&self) -> String {
+        self.logs()
+            .iter()
+            .map(snapshot_line)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn snapshot_line(log: &CreateLogArgs) -> String {
+    let mut line = format!(
+        "{:<5} {}",
+        log.level.as_str().to_ascii_uppercase(),
+        log.message
+    );
+    if let Some(ref flow_id) = log.flow_id {
+        line.push_str(&format!(
+            " flow={flow_id}#{}",
+            log.step_index.unwrap_or_default()
+        ));
+    }
+    if let Some(ref tags) = log.tags {
+        line.push_str(&format!(" tags=[{}]", tags.join(",")));
+    }
+    if let Some(ref data) = log.data {
+        let sorted: BTreeMap<_, _> = data.iter().collect();
+        line.push_str(&format!(
+            " data={}",
+            serde_json::to_string(&sorted).unwrap_or_default()
+        ));
+    }
+    line
+}
@@ -0,0 +1,164 @@
+use std::future::Future;
+
+use crate::error::TimberlogsError;
+
+const MAX_TRACESTATE_MEMBERS: usize = 32;
+
+tokio::task_local! {
+    static CURRENT: TraceContext;
+}
+
+/// A W3C trace context, as carried by the `traceparent` and `tracestate`
+/// headers.
+///
+/// Logs written inside [`TraceContext::scope`] pick up its trace and span ids
+/// unless the entry sets its own `trace_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub trace_flags: u8,
+    pub trace_state: Vec<(String, String)>,
+}
+
+impl TraceContext {
+    pub const FLAG_SAMPLED: u8 = 0x01;
+
+    /// Starts a new sampled trace with random ids.
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: format!("{:032x}", random_nonzero_u128()),
+            span_id: new_span_id(),
+            parent_span_id: None,
+            trace_flags: Self::FLAG_SAMPLED,
+            trace_state: Vec::new(),
+        }
+    }
+
+    /// Parses a `traceparent` header. The header's parent id becomes
+    /// `span_id`; call [`TraceContext::child`] to start a span of your own.
+    pub fn from_traceparent(header: &str) -> Result<Self, TimberlogsError> {
+        let invalid = || TimberlogsError::Validation(format!("invalid traceparent: {header}"));
+
+        let parts: Vec<&str> = header.trim().split('-').collect();
+        let [version, trace_id, span_id, flags, ..] = parts[..] else {
+            return Err(invalid());
+        };
+        if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.len() != 4) {
+            return Err(invalid());
+        }
+        if !is_hex(trace_id, 32) || is_zero(trace_id) || !is_hex(span_id, 16) || is_zero(span_id) {
+            return Err(invalid());
+        }
+        if !is_hex(flags, 2) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            parent_span_id: None,
+            trace_flags: u8::from_str_radix(flags, 16).map_err(|_| invalid())?,
+            trace_state: Vec::new(),
+        })
+    }
+
+    /// Attaches the vendor entries from a `tracestate` header.
+    pub fn with_tracestate(mut self, header: &str) -> Result<Self, TimberlogsError> {
+        self.trace_state = parse_tracestate(header)?;
+        Ok(self)
+    }
+
+    pub fn to_traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id, self.span_id, self.trace_flags
+        )
+    }
+
+    pub fn to_tracestate(&self) -> Option<String> {
+        if self.trace_state.is_empty() {
+            return None;
+        }
+        Some(
+            self.trace_state
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(","),
+        )
+    }
+
+    /// Returns a context for a new span in the same trace, parented to this one.
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id.clone(),
+            span_id: new_span_id(),
+            parent_span_id: Some(self.span_id.clone()),
+            trace_flags: self.trace_flags,
+            trace_state: self.trace_state.clone(),
+        }
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.trace_flags & Self::FLAG_SAMPLED != 0
+    }
+
+    /// Runs `future` with this context as the current trace context.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// The context set by the enclosing [`TraceContext::scope`], if any.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+}
+
+fn parse_tracestate(header: &str) -> Result<Vec<(String, String)>, TimberlogsError> {
+    let mut members = Vec::new();
+    for member in header.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+        let (key, value) = member
+            .split_once('=')
+            .filter(|(key, value)| !key.is_empty() && !value.is_empty() && !value.contains('='))
+            .ok_or_else(|| {
+                TimberlogsError::Validation(format!("invalid tracestate member: {member}"))
+            })?;
+        members.push((key.to_string(), value.to_string()));
+    }
+    if members.len() > MAX_TRACESTATE_MEMBERS {
+        return Err(TimberlogsError::Validation(format!(
+            "tracestate must have at most {MAX_TRACESTATE_MEMBERS} members, got {}",
+            members.len()
+        )));
+    }
+    Ok(members)
+}
+
+pub(crate) fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn is_zero(value: &str) -> bool {
+    value.bytes().all(|b| b == b'0')
+}
+
+fn new_span_id() -> String {
+    let mut id = 0;
+    while id == 0 {
+        id = rand::random::<u64>();
+    }
+    format!("{id:016x}")
+}
+
+fn random_nonzero_u128() -> u128 {
+    let mut id = 0;
+    while id == 0 {
+        id = rand::random::<u128>();
+    }
+    id
+}
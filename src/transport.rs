@@ -0,0 +1,297 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+
+use crate::error::TimberlogsError;
+use crate::executor::on_tokio;
+use crate::types::{
+    ApiLimits, BatchPayload, CreateLogArgs, FlowResponse, IngestRawOptions, IngestResponse,
+    RawFormat, VerifyReport, VerifyResponse,
+};
+
+pub(crate) const LOGS_PATH: &str = "/v1/logs";
+pub(crate) const FLOWS_PATH: &str = "/v1/flows";
+pub(crate) const TAIL_PATH: &str = "/v1/logs/tail";
+pub(crate) const VERIFY_PATH: &str = "/v1/verify";
+
+const IDEMPOTENCY_NAMESPACE: uuid::Uuid =
+    uuid::Uuid::from_u128(0x5dd0027f_9337_4701_9b26_e08699eb989c);
+
+/// A key for the `Idempotency-Key` header, derived from the entries so that
+/// every retry of the same batch sends the same key.
+pub fn idempotency_key(logs: &[CreateLogArgs]) -> String {
+    let mut name = Vec::new();
+    for log in logs {
+        match log.id {
+            Some(ref id) => name.extend_from_slice(id.as_bytes()),
+            None => name.extend(serde_json::to_vec(log).unwrap_or_default()),
+        }
+        name.push(b'\n');
+    }
+    uuid::Uuid::new_v5(&IDEMPOTENCY_NAMESPACE, &name).to_string()
+}
+
+/// Delivers logs and flows to a backend.
+///
+/// Each call is a single attempt; the client handles batching, retries and
+/// requeueing around it.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Entries listed in the response's `rejected` are dead-lettered rather
+    /// than retried.
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError>;
+
+    async fn ingest_raw(
+        &self,
+        body: &str,
+        format: RawFormat,
+        options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError>;
+
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError>;
+
+    /// Checks that the backend is reachable and accepts the credentials.
+    async fn verify(&self) -> Result<VerifyReport, TimberlogsError> {
+        Err(TimberlogsError::Validation(
+            "verify is not supported by this transport".into(),
+        ))
+    }
+}
+
+/// The default transport, talking to the Timberlogs ingest API over HTTP.
+pub struct HttpTransport {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl HttpTransport {
+    pub fn new(base_url: impl Into<String>, api_key: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into(),
+            api_key: api_key.into(),
+        }
+    }
+}
+
+/// Sends `request`, on a tokio runtime even when called from elsewhere.
+pub(crate) async fn send(
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, TimberlogsError> {
+    Ok(on_tokio(request.send()).await?)
+}
+
+pub(crate) async fn error_for_status(
+    response: reqwest::Response,
+) -> Result<reqwest::Response, TimberlogsError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status().as_u16();
+    let body = response.text().await.unwrap_or_default();
+    Err(TimberlogsError::Http { status, body })
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError> {
+        let response = send(
+            self.http
+                .post(format!("{}{}", self.base_url, LOGS_PATH))
+                .header("Content-Type", "application/json")
+                .header("X-API-Key", &self.api_key)
+                .header("Idempotency-Key", idempotency_key(logs))
+                .json(&BatchPayload { logs }),
+        )
+        .await?;
+        let response = error_for_status(response).await?;
+        // The batch is accepted at this point. A body that can't be read only
+        // loses the `rejected` list; failing here would send the batch again.
+        let body = response.bytes().await.unwrap_or_default();
+        Ok(serde_json::from_slice(&body).unwrap_or_else(|_| IngestResponse::accepted(logs.len())))
+    }
+
+    async fn ingest_raw(
+        &self,
+        body: &str,
+        format: RawFormat,
+        options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError> {
+        let mut url = format!("{}{}?format={}", self.base_url, LOGS_PATH, format.as_str());
+        if let Some(ref source) = options.source {
+            url.push_str(&format!("&source={}", urlencoding::encode(source)));
+        }
+        if let Some(ref env) = options.environment {
+            url.push_str(&format!(
+                "&environment={}",
+                urlencoding::encode(env.as_str())
+            ));
+        }
+        if let Some(level) = options.level {
+            url.push_str(&format!("&level={}", urlencoding::encode(level.as_str())));
+        }
+        if let Some(ref dataset) = options.dataset {
+            url.push_str(&format!("&dataset={}", urlencoding::encode(dataset)));
+        }
+
+        let response = send(
+            self.http
+                .post(&url)
+                .header("Content-Type", format.content_type())
+                .header("X-API-Key", &self.api_key)
+                .body(body.to_string()),
+        )
+        .await?;
+        error_for_status(response).await?;
+        Ok(())
+    }
+
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError> {
+        let response = send(
+            self.http
+                .post(format!("{}{}", self.base_url, FLOWS_PATH))
+                .header("Content-Type", "application/json")
+                .header("X-API-Key", &self.api_key)
+                .json(&serde_json::json!({ "name": name })),
+        )
+        .await?;
+        Ok(error_for_status(response).await?.json().await?)
+    }
+
+    async fn verify(&self) -> Result<VerifyReport, TimberlogsError> {
+        let endpoint = format!("{}{}", self.base_url, VERIFY_PATH);
+        let started = Instant::now();
+        let response = send(self.http.get(&endpoint).header("X-API-Key", &self.api_key)).await?;
+        let latency = started.elapsed();
+        let clock_skew_ms = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|date| httpdate::parse_http_date(date.to_str().ok()?).ok())
+            .map(|server_time| millis_between(SystemTime::now(), server_time));
+
+        let status = response.status().as_u16();
+        let (authenticated, limits) = if status == 401 || status == 403 {
+            (false, ApiLimits::default())
+        } else {
+            let body: VerifyResponse = error_for_status(response).await?.json().await?;
+            (true, body.limits)
+        };
+        Ok(VerifyReport {
+            authenticated,
+            endpoint,
+            latency,
+            clock_skew_ms,
+            limits,
+        })
+    }
+}
+
+/// `to - from` in milliseconds, negative if `to` is earlier.
+fn millis_between(from: SystemTime, to: SystemTime) -> i64 {
+    match to.duration_since(from) {
+        Ok(ahead) => ahead.as_millis() as i64,
+        Err(behind) => -(behind.duration().as_millis() as i64),
+    }
+}
+
+/// A raw ingestion recorded by [`InMemoryTransport`].
+#[derive(Debug, Clone)]
+pub struct RawIngest {
+    pub body: String,
+    pub format: RawFormat,
+    pub options: IngestRawOptions,
+}
+
+#[derive(Default)]
+struct Recorded {
+    batches: Vec<Vec<CreateLogArgs>>,
+    raw: Vec<RawIngest>,
+    flows: Vec<FlowResponse>,
+}
+
+/// A transport that keeps everything in memory, for asserting on logging in
+/// tests without an HTTP server.
+///
+/// Flow ids are `"{name}-{n}"`, where `n` counts flows created so far.
+#[derive(Default)]
+pub struct InMemoryTransport {
+    recorded: Mutex<Recorded>,
+}
+
+impl InMemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn batches(&self) -> Vec<Vec<CreateLogArgs>> {
+        self.recorded.lock().unwrap().batches.clone()
+    }
+
+    /// Every log sent so far, in order, across all batches.
+    pub fn logs(&self) -> Vec<CreateLogArgs> {
+        self.recorded
+            .lock()
+            .unwrap()
+            .batches
+            .iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    pub fn raw_ingests(&self) -> Vec<RawIngest> {
+        self.recorded.lock().unwrap().raw.clone()
+    }
+
+    pub fn flows(&self) -> Vec<FlowResponse> {
+        self.recorded.lock().unwrap().flows.clone()
+    }
+
+    pub fn clear(&self) {
+        *self.recorded.lock().unwrap() = Recorded::default();
+    }
+}
+
+#[async_trait]
+impl Transport for InMemoryTransport {
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError> {
+        self.recorded.lock().unwrap().batches.push(logs.to_vec());
+        Ok(IngestResponse::accepted(logs.len()))
+    }
+
+    async fn ingest_raw(
+        &self,
+        body: &str,
+        format: RawFormat,
+        options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError> {
+        self.recorded.lock().unwrap().raw.push(RawIngest {
+            body: body.to_string(),
+            format,
+            options: options.clone(),
+        });
+        Ok(())
+    }
+
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError> {
+        let mut recorded = self.recorded.lock().unwrap();
+        let flow = FlowResponse {
+            flow_id: format!("{name}-{}", recorded.flows.len()),
+            name: name.to_string(),
+        };
+        recorded.flows.push(flow.clone());
+        Ok(flow)
+    }
+
+    async fn verify(&self) -> Result<VerifyReport, TimberlogsError> {
+        Ok(VerifyReport {
+            authenticated: true,
+            endpoint: "memory".into(),
+            latency: Duration::ZERO,
+            clock_skew_ms: Some(0),
+            limits: ApiLimits::default(),
+        })
+    }
+}
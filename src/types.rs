@@ -1,26 +1,165 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+use crate::error::TimberlogsError;
+use crate::trace::is_hex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel {
+    Trace,
     Debug,
     Info,
     Warn,
     Error,
+    Fatal,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+            LogLevel::Fatal => "fatal",
+        }
+    }
+
+    /// The closest level accepted by servers that only know `debug`, `info`,
+    /// `warn` and `error`.
+    pub fn legacy(&self) -> LogLevel {
+        match self {
+            LogLevel::Trace => LogLevel::Debug,
+            LogLevel::Fatal => LogLevel::Error,
+            level => *level,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = TimberlogsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "trace" => Ok(LogLevel::Trace),
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            "fatal" | "critical" => Ok(LogLevel::Fatal),
+            _ => Err(TimberlogsError::Validation(format!(
+                "unknown log level: {}",
+                s.trim()
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Environment {
     Development,
     Staging,
     Production,
+    /// Any other environment name, such as `qa` or `preview-123`. Names must
+    /// be 1-50 characters of lowercase letters, digits, `-`, `_` or `.`.
+    Custom(String),
+}
+
+impl Environment {
+    /// Builds an environment from `name`, using a built-in variant when the
+    /// name matches one and validating it otherwise.
+    pub fn custom(name: impl Into<String>) -> Result<Self, TimberlogsError> {
+        let name = name.into();
+        let env = match name.as_str() {
+            "development" => Environment::Development,
+            "staging" => Environment::Staging,
+            "production" => Environment::Production,
+            _ => Environment::Custom(name),
+        };
+        env.validate()?;
+        Ok(env)
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Environment::Development => "development",
+            Environment::Staging => "staging",
+            Environment::Production => "production",
+            Environment::Custom(name) => name,
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), TimberlogsError> {
+        let Environment::Custom(name) = self else {
+            return Ok(());
+        };
+        if name.is_empty() || name.len() > 50 {
+            return Err(TimberlogsError::Validation(format!(
+                "environment must be 1-50 characters, got {}",
+                name.len()
+            )));
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(TimberlogsError::Validation(format!(
+                "environment may only contain lowercase letters, digits, '-', '_' and '.': {name}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Environment {
+    type Err = TimberlogsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "dev" => Ok(Environment::Development),
+            "prod" => Ok(Environment::Production),
+            name => Environment::custom(name),
+        }
+    }
+}
+
+impl Serialize for Environment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Environment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Environment::custom(name).map_err(serde::de::Error::custom)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LogEntry {
+    /// Defaults to a random UUID. Set it to something stable to have the
+    /// server drop repeated sends of the same entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub level: LogLevel,
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -49,11 +188,24 @@ pub struct LogEntry {
     pub ip_address: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_flags: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct CreateLogArgs {
+pub struct CreateLogArgs {
+    /// Unique per entry so the server can drop duplicates of it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub level: LogLevel,
     pub message: String,
     pub source: String,
@@ -86,28 +238,182 @@ pub(crate) struct CreateLogArgs {
     pub ip_address: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_flags: Option<u8>,
+    /// Set on throttling summaries: the number of occurrences represented by
+    /// this entry, including itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppressed_count: Option<u32>,
+}
+
+impl CreateLogArgs {
+    /// Checks the field limits enforced by the ingest API.
+    pub fn validate(&self) -> Result<(), TimberlogsError> {
+        if self.message.is_empty() {
+            return Err(TimberlogsError::Validation(
+                "message must not be empty".into(),
+            ));
+        }
+        if self.message.len() > 10_000 {
+            return Err(TimberlogsError::Validation(format!(
+                "message exceeds 10000 characters: {}",
+                self.message.len()
+            )));
+        }
+        check_str(self.user_id.as_deref(), "user_id", 100)?;
+        check_str(self.session_id.as_deref(), "session_id", 100)?;
+        check_str(self.request_id.as_deref(), "request_id", 100)?;
+        check_str(self.error_name.as_deref(), "error_name", 200)?;
+        check_str(self.error_stack.as_deref(), "error_stack", 10_000)?;
+        check_str(self.flow_id.as_deref(), "flow_id", 50)?;
+        check_str(self.dataset.as_deref(), "dataset", 50)?;
+        check_str(self.ip_address.as_deref(), "ip_address", 100)?;
+        check_str(self.country.as_deref(), "country", 10)?;
+        check_hex(self.trace_id.as_deref(), "trace_id", 32)?;
+        check_hex(self.span_id.as_deref(), "span_id", 16)?;
+        check_hex(self.parent_span_id.as_deref(), "parent_span_id", 16)?;
+        if let Some(ref tags) = self.tags {
+            if tags.len() > 20 {
+                return Err(TimberlogsError::Validation(format!(
+                    "tags must have at most 20 items, got {}",
+                    tags.len()
+                )));
+            }
+            for (i, tag) in tags.iter().enumerate() {
+                if tag.len() > 50 {
+                    return Err(TimberlogsError::Validation(format!(
+                        "tags[{i}] exceeds 50 characters: {}",
+                        tag.len()
+                    )));
+                }
+            }
+        }
+        if let Some(step) = self.step_index {
+            if step > 1000 {
+                return Err(TimberlogsError::Validation(format!(
+                    "step_index must be 0-1000, got {step}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Downgrades `Trace`/`Fatal` to a legacy level, keeping the original
+    /// level in `data.originalLevel`.
+    pub fn into_legacy_level(mut self) -> Self {
+        let legacy = self.level.legacy();
+        if legacy != self.level {
+            self.data
+                .get_or_insert_with(HashMap::new)
+                .insert("originalLevel".into(), self.level.as_str().into());
+            self.level = legacy;
+        }
+        self
+    }
+}
+
+fn check_str(value: Option<&str>, name: &str, max_len: usize) -> Result<(), TimberlogsError> {
+    if let Some(v) = value {
+        if v.len() > max_len {
+            return Err(TimberlogsError::Validation(format!(
+                "{name} exceeds {max_len} characters: {}",
+                v.len()
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn check_hex(value: Option<&str>, name: &str, len: usize) -> Result<(), TimberlogsError> {
+    if let Some(v) = value {
+        if !is_hex(v, len) {
+            return Err(TimberlogsError::Validation(format!(
+                "{name} must be {len} lowercase hex characters: {v}"
+            )));
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, Serialize)]
-pub(crate) struct BatchPayload {
-    pub logs: Vec<CreateLogArgs>,
+pub(crate) struct BatchPayload<'a> {
+    pub logs: &'a [CreateLogArgs],
 }
 
-#[derive(Debug, Deserialize)]
-pub(crate) struct IngestResponse {
-    #[allow(dead_code)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestResponse {
     pub success: bool,
-    #[allow(dead_code)]
     pub count: u32,
+    /// Entries the server refused while accepting the rest of the batch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected: Vec<RejectedEntry>,
+}
+
+impl IngestResponse {
+    /// A response accepting all `count` entries.
+    pub fn accepted(count: usize) -> Self {
+        Self {
+            success: true,
+            count: count as u32,
+            rejected: Vec::new(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedEntry {
+    /// Position of the entry in the batch.
+    pub index: usize,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct FlowResponse {
+pub struct FlowResponse {
     pub flow_id: String,
     pub name: String,
 }
 
+/// Limits the ingest API applies to this API key, as far as it reports them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_batch_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_batch_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+}
+
+/// Body of a successful `GET /v1/verify`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    #[serde(default)]
+    pub limits: ApiLimits,
+}
+
+/// Result of [`TimberlogsClient::verify`](crate::TimberlogsClient::verify).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Whether the API accepted the key. A rejected key is reported here
+    /// rather than as an error.
+    pub authenticated: bool,
+    /// The URL that answered.
+    pub endpoint: String,
+    pub latency: Duration,
+    /// Server time minus local time, from the response's `Date` header, in
+    /// milliseconds. The header only has second precision.
+    pub clock_skew_ms: Option<i64>,
+    pub limits: ApiLimits,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RawFormat {
     Json,
@@ -142,7 +448,26 @@ impl RawFormat {
     }
 }
 
-#[derive(Debug, Default)]
+impl FromStr for RawFormat {
+    type Err = TimberlogsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(RawFormat::Json),
+            "jsonl" | "ndjson" => Ok(RawFormat::Jsonl),
+            "syslog" => Ok(RawFormat::Syslog),
+            "text" => Ok(RawFormat::Text),
+            "csv" => Ok(RawFormat::Csv),
+            "obl" => Ok(RawFormat::Obl),
+            _ => Err(TimberlogsError::Validation(format!(
+                "unknown raw format: {}",
+                s.trim()
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct IngestRawOptions {
     pub source: Option<String>,
     pub environment: Option<Environment>,
@@ -0,0 +1,265 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use timberlogs::{
+    Environment, FileAgent, FileAgentConfig, InMemoryTransport, LogLevel, RawFormat,
+    TimberlogsClient, TimberlogsConfig, TimberlogsError, WatchedFile,
+};
+
+fn client(transport: &Arc<InMemoryTransport>) -> Arc<TimberlogsClient> {
+    Arc::new(TimberlogsClient::new(TimberlogsConfig {
+        source: "agent".into(),
+        environment: Environment::Development,
+        api_key: "tb_test_key".into(),
+        flush_interval_ms: Some(60000),
+        transport: Some(transport.clone()),
+        ..Default::default()
+    }))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("timberlogs-agent-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn agent(
+    transport: &Arc<InMemoryTransport>,
+    dir: &Path,
+    file: WatchedFile,
+    read_existing: bool,
+) -> FileAgent {
+    FileAgent::new(
+        client(transport),
+        FileAgentConfig {
+            files: vec![file],
+            checkpoint_path: dir.join("checkpoint.json"),
+            read_existing,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+fn append(path: &Path, text: &str) {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    file.write_all(text.as_bytes()).unwrap();
+}
+
+fn messages(transport: &InMemoryTransport) -> Vec<String> {
+    transport
+        .logs()
+        .into_iter()
+        .map(|log| log.message)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_agent_ships_new_complete_lines() {
+    let dir = temp_dir("lines");
+    let path = dir.join("app.log");
+    append(&path, "written before startup\n");
+    let transport = Arc::new(InMemoryTransport::new());
+    let mut agent = agent(
+        &transport,
+        &dir,
+        WatchedFile::new(&path, RawFormat::Text),
+        false,
+    );
+
+    assert_eq!(agent.poll().await.unwrap(), 0);
+    append(&path, "first\n\nsecond\r\nthird, still being writ");
+    assert_eq!(agent.poll().await.unwrap(), 2);
+    append(&path, "ten\n");
+    assert_eq!(agent.poll().await.unwrap(), 1);
+
+    assert_eq!(
+        messages(&transport),
+        ["first", "second", "third, still being written"]
+    );
+    let log = &transport.logs()[0];
+    assert_eq!(log.level, LogLevel::Info);
+    assert_eq!(
+        log.data.as_ref().unwrap()["file"],
+        path.display().to_string()
+    );
+}
+
+// Rotation is told apart by inode.
+#[cfg(unix)]
+#[tokio::test]
+async fn test_agent_follows_rotation_and_truncation() {
+    let dir = temp_dir("rotation");
+    let path = dir.join("app.log");
+    append(&path, "one\n");
+    let transport = Arc::new(InMemoryTransport::new());
+    let mut agent = agent(
+        &transport,
+        &dir,
+        WatchedFile::new(&path, RawFormat::Text),
+        true,
+    );
+    agent.poll().await.unwrap();
+
+    // Written to the old file after it was renamed, before the agent noticed.
+    fs::rename(&path, dir.join("app.log.1")).unwrap();
+    append(&dir.join("app.log.1"), "two\nthree without newline");
+    append(&path, "four\n");
+    agent.poll().await.unwrap();
+    assert_eq!(
+        messages(&transport),
+        ["one", "two", "three without newline", "four"]
+    );
+
+    fs::write(&path, "5\n").unwrap();
+    agent.poll().await.unwrap();
+    assert_eq!(messages(&transport).last().unwrap(), "5");
+}
+
+#[tokio::test]
+async fn test_agent_detects_copytruncate_past_old_offset() {
+    let dir = temp_dir("copytruncate");
+    let path = dir.join("app.log");
+    append(&path, "before truncate\n");
+    let transport = Arc::new(InMemoryTransport::new());
+    let mut agent = agent(
+        &transport,
+        &dir,
+        WatchedFile::new(&path, RawFormat::Text),
+        true,
+    );
+    agent.poll().await.unwrap();
+
+    // Truncated and written to again, past the old offset, between polls.
+    fs::write(&path, "new\nand already longer\n").unwrap();
+    agent.poll().await.unwrap();
+
+    assert_eq!(
+        messages(&transport),
+        ["before truncate", "new", "and already longer"]
+    );
+    let logs = transport.logs();
+    assert_ne!(logs[0].id, logs[1].id);
+}
+
+#[tokio::test]
+async fn test_agent_resumes_from_checkpoint_with_stable_ids() {
+    let dir = temp_dir("checkpoint");
+    let path = dir.join("app.log");
+    append(&path, "one\ntwo\n");
+    let first = Arc::new(InMemoryTransport::new());
+    agent(&first, &dir, WatchedFile::new(&path, RawFormat::Text), true)
+        .poll()
+        .await
+        .unwrap();
+
+    append(&path, "three\n");
+    let second = Arc::new(InMemoryTransport::new());
+    agent(
+        &second,
+        &dir,
+        WatchedFile::new(&path, RawFormat::Text),
+        true,
+    )
+    .poll()
+    .await
+    .unwrap();
+    assert_eq!(messages(&first), ["one", "two"]);
+    assert_eq!(messages(&second), ["three"]);
+
+    // Without the checkpoint every line is read again, with the ids it was
+    // sent with the first time.
+    fs::remove_file(dir.join("checkpoint.json")).unwrap();
+    let again = Arc::new(InMemoryTransport::new());
+    agent(&again, &dir, WatchedFile::new(&path, RawFormat::Text), true)
+        .poll()
+        .await
+        .unwrap();
+    let ids = |transport: &InMemoryTransport| -> Vec<String> {
+        transport
+            .logs()
+            .into_iter()
+            .map(|log| log.id.unwrap())
+            .collect()
+    };
+    assert_eq!(ids(&again), [ids(&first), ids(&second)].concat());
+}
+
+#[tokio::test]
+async fn test_agent_parses_jsonl_and_syslog() {
+    let dir = temp_dir("formats");
+    let jsonl = dir.join("app.jsonl");
+    let syslog = dir.join("messages");
+    append(
+        &jsonl,
+        concat!(
+            r#"{"msg":"cache miss","level":"warning","timestamp":1700000000000,"key":"user:1"}"#,
+            "\n",
+            "not json\n",
+        ),
+    );
+    append(
+        &syslog,
+        concat!(
+            "<11>1 2024-05-01T12:30:00Z web1 nginx 812 - [meta x=\"a\\]b\"] upstream timed out\n",
+            "<30>Oct  1 09:30:00 db1 postgres[77]: checkpoint complete\n",
+        ),
+    );
+    let transport = Arc::new(InMemoryTransport::new());
+    let mut agent = FileAgent::new(
+        client(&transport),
+        FileAgentConfig {
+            files: vec![
+                WatchedFile {
+                    level: LogLevel::Debug,
+                    dataset: Some("cache".into()),
+                    ..WatchedFile::new(&jsonl, RawFormat::Jsonl)
+                },
+                WatchedFile::new(&syslog, RawFormat::Syslog),
+            ],
+            checkpoint_path: dir.join("checkpoint.json"),
+            read_existing: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(agent.poll().await.unwrap(), 4);
+
+    let logs = transport.logs();
+    assert_eq!(logs[0].message, "cache miss");
+    assert_eq!(logs[0].level, LogLevel::Warn);
+    assert_eq!(logs[0].timestamp, Some(1_700_000_000_000));
+    assert_eq!(logs[0].data.as_ref().unwrap()["key"], "user:1");
+    assert_eq!(logs[0].dataset.as_deref(), Some("cache"));
+    assert_eq!(logs[1].message, "not json");
+    assert_eq!(logs[1].level, LogLevel::Debug);
+
+    assert_eq!(logs[2].message, "upstream timed out");
+    assert_eq!(logs[2].level, LogLevel::Error);
+    assert_eq!(logs[2].data.as_ref().unwrap()["hostname"], "web1");
+    assert_eq!(logs[2].data.as_ref().unwrap()["appName"], "nginx");
+    assert_eq!(logs[3].message, "checkpoint complete");
+    assert_eq!(logs[3].level, LogLevel::Info);
+    assert_eq!(logs[3].data.as_ref().unwrap()["appName"], "postgres");
+}
+
+#[tokio::test]
+async fn test_agent_rejects_unsupported_formats() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let result = FileAgent::new(
+        client(&transport),
+        FileAgentConfig {
+            files: vec![WatchedFile::new("report.csv", RawFormat::Csv)],
+            checkpoint_path: temp_dir("csv").join("checkpoint.json"),
+            ..Default::default()
+        },
+    );
+    assert!(matches!(result, Err(TimberlogsError::Validation(_))));
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "blocking")]
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use timberlogs::{
+    BlockingTimberlogsClient, CreateLogArgs, Environment, FlowResponse, InMemoryTransport,
+    IngestRawOptions, IngestResponse, LogLevel, RawFormat, TimberlogsConfig, TimberlogsError,
+    Transport,
+};
+
+fn blocking_config(transport: &Arc<InMemoryTransport>) -> TimberlogsConfig {
+    TimberlogsConfig {
+        source: "cli".into(),
+        environment: Environment::Development,
+        batch_size: Some(10),
+        flush_interval_ms: Some(60000),
+        transport: Some(transport.clone()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_blocking_client_logs_without_runtime() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let mut client = BlockingTimberlogsClient::new(blocking_config(&transport));
+
+    client.info("starting", None).unwrap();
+    client.set_min_level(LogLevel::Warn);
+    client.debug("filtered", None).unwrap();
+    client.error("failed", None).unwrap();
+
+    client.flush().unwrap();
+    let messages: Vec<_> = transport
+        .logs()
+        .into_iter()
+        .map(|log| log.message)
+        .collect();
+    assert_eq!(messages, ["starting", "failed"]);
+
+    client.warn("after flush", None).unwrap();
+    client.disconnect().unwrap();
+    assert_eq!(transport.logs().len(), 3);
+    assert!(client.info("closed", None).is_err());
+}
+
+#[test]
+fn test_blocking_flow_and_ingest_raw() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let client = BlockingTimberlogsClient::new(TimberlogsConfig {
+        min_level: Some(LogLevel::Info),
+        ..blocking_config(&transport)
+    });
+
+    let mut flow = client.flow("backup").unwrap();
+    flow.debug("skipped", None).unwrap();
+    flow.info("copying", None)
+        .unwrap()
+        .info("done", None)
+        .unwrap();
+    assert_eq!(flow.step_index(), 2);
+    client.flush().unwrap();
+
+    let steps: Vec<_> = transport
+        .logs()
+        .into_iter()
+        .map(|log| log.step_index)
+        .collect();
+    assert_eq!(steps, [Some(0), Some(1)]);
+    assert_eq!(transport.logs()[0].flow_id.as_deref(), Some("backup-0"));
+
+    client
+        .ingest_raw("line one", RawFormat::Text, None)
+        .unwrap();
+    assert_eq!(transport.raw_ingests()[0].body, "line one");
+
+    assert!(client.log(timberlogs::LogEntry::default()).is_err());
+}
+
+#[test]
+#[should_panic(expected = "batch_size must be greater than 0")]
+fn test_blocking_client_propagates_config_panics() {
+    BlockingTimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(0),
+        ..Default::default()
+    });
+}
+
+// Takes `delay` to accept each batch.
+struct SlowTransport {
+    delay: Duration,
+    recorded: InMemoryTransport,
+}
+
+#[async_trait::async_trait]
+impl Transport for SlowTransport {
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError> {
+        tokio::time::sleep(self.delay).await;
+        self.recorded.send_batch(logs).await
+    }
+
+    async fn ingest_raw(
+        &self,
+        body: &str,
+        format: RawFormat,
+        options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError> {
+        self.recorded.ingest_raw(body, format, options).await
+    }
+
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError> {
+        self.recorded.create_flow(name).await
+    }
+}
+
+#[test]
+fn test_blocking_log_does_not_wait_for_slow_transport() {
+    let transport = Arc::new(SlowTransport {
+        delay: Duration::from_millis(300),
+        recorded: InMemoryTransport::new(),
+    });
+    let mut client = BlockingTimberlogsClient::new(TimberlogsConfig {
+        source: "cli".into(),
+        batch_size: Some(1),
+        flush_interval_ms: Some(60000),
+        transport: Some(transport.clone()),
+        ..Default::default()
+    });
+
+    // Every entry fills a batch, so each one starts a 300ms send.
+    let started = Instant::now();
+    std::thread::scope(|scope| {
+        for thread in 0..2 {
+            let client = &client;
+            scope.spawn(move || {
+                for i in 0..3 {
+                    client.info(format!("{thread}-{i}"), None).unwrap();
+                }
+            });
+        }
+    });
+    assert!(
+        started.elapsed() < Duration::from_millis(200),
+        "log calls took {:?}",
+        started.elapsed()
+    );
+
+    client.flush().unwrap();
+    assert_eq!(transport.recorded.logs().len(), 6);
+    client.disconnect().unwrap();
+}
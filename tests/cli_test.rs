@@ -0,0 +1,295 @@
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use mockito::Matcher;
+
+fn timberlogs(server: &mockito::Server, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_timberlogs"))
+        .args(args)
+        .env("TIMBERLOGS_API_KEY", "tb_cli_key")
+        .env("TIMBERLOGS_BASE_URL", server.url())
+        .env("TIMBERLOGS_SOURCE", "nightly-backup")
+        .env("TIMBERLOGS_ENVIRONMENT", "staging")
+        .env_remove("TIMBERLOGS_DATASET")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_send_uses_env_config_and_flags() {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .match_header("X-API-Key", "tb_cli_key")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "logs": [{
+                "level": "warn",
+                "message": "backup slow",
+                "source": "nightly-backup",
+                "environment": "staging",
+                "data": { "seconds": 812, "host": "db1" },
+                "tags": ["cron", "backup"],
+            }]
+        })))
+        .with_body(r#"{"success":true,"count":1}"#)
+        .create();
+
+    let output = timberlogs(
+        &server,
+        &[
+            "send",
+            "backup slow",
+            "--level",
+            "warn",
+            "--data",
+            "seconds=812",
+            "--data",
+            "host=db1",
+            "--tag",
+            "cron",
+            "--tag",
+            "backup",
+        ],
+        "",
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    mock.assert();
+}
+
+#[test]
+fn test_send_fails_when_log_is_rejected() {
+    let mut server = mockito::Server::new();
+    server
+        .mock("POST", "/v1/logs")
+        .with_status(400)
+        .with_body(r#"{"error":"logs[0]: message must not be empty"}"#)
+        .create();
+
+    let output = timberlogs(&server, &["send", "oops"], "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("log was rejected"), "{stderr}");
+}
+
+#[test]
+fn test_ingest_reads_stdin_in_raw_format() {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("format".into(), "csv".into()),
+            Matcher::UrlEncoded("source".into(), "nightly-backup".into()),
+            Matcher::UrlEncoded("environment".into(), "staging".into()),
+            Matcher::UrlEncoded("level".into(), "error".into()),
+        ]))
+        .match_header("Content-Type", "text/csv")
+        .match_body("name,status\nusers,failed\n")
+        .with_body(r#"{"success":true,"count":1}"#)
+        .create();
+
+    let output = timberlogs(
+        &server,
+        &["ingest", "--format", "csv", "--level", "error"],
+        "name,status\nusers,failed\n",
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    mock.assert();
+}
+
+#[test]
+fn test_flow_start_prints_id_and_log_sends_step() {
+    let mut server = mockito::Server::new();
+    server
+        .mock("POST", "/v1/flows")
+        .match_body(Matcher::Json(serde_json::json!({ "name": "backup" })))
+        .with_body(r#"{"flowId":"backup-1a2b3c4d","name":"backup"}"#)
+        .create();
+    let step = server
+        .mock("POST", "/v1/logs")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "logs": [{ "message": "dumped users", "flowId": "backup-1a2b3c4d", "stepIndex": 2 }]
+        })))
+        .with_body(r#"{"success":true,"count":1}"#)
+        .create();
+
+    let output = timberlogs(&server, &["flow", "start", "backup"], "");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "backup-1a2b3c4d\n");
+
+    let output = timberlogs(
+        &server,
+        &["flow", "log", "backup-1a2b3c4d", "2", "dumped users"],
+        "",
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    step.assert();
+}
+
+#[test]
+fn test_flow_show_prints_steps_in_order() {
+    let mut server = mockito::Server::new();
+    let step = |index: u32, message: &str| {
+        serde_json::json!({
+            "id": format!("s{index}"),
+            "timestamp": 1_700_000_000_250u64 + u64::from(index),
+            "level": "info",
+            "message": message,
+            "source": "nightly-backup",
+            "environment": "staging",
+            "flowId": "backup-1",
+            "stepIndex": index,
+        })
+    };
+    server
+        .mock("GET", "/v1/flows/backup-1")
+        .with_body(
+            serde_json::json!({
+                "flowId": "backup-1",
+                "name": "backup",
+                "createdAt": 1_700_000_000_000u64,
+                "steps": [step(1, "upload"), step(0, "dump")],
+            })
+            .to_string(),
+        )
+        .create();
+
+    let output = timberlogs(&server, &["flow", "show", "backup-1"], "");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines[0],
+        "backup (backup-1), created 2023-11-14T22:13:20.000Z"
+    );
+    assert_eq!(
+        lines[1],
+        "2023-11-14T22:13:20.250Z INFO  [nightly-backup] #0 dump"
+    );
+    assert_eq!(
+        lines[2],
+        "2023-11-14T22:13:20.251Z INFO  [nightly-backup] #1 upload"
+    );
+}
+
+// Interrupted with SIGTERM, as a service manager would stop it.
+#[cfg(unix)]
+#[test]
+fn test_agent_ships_file_and_checkpoints_on_interrupt() {
+    let dir = std::env::temp_dir().join(format!("timberlogs-cli-agent-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.log");
+    std::fs::write(&path, "disk 91% full\n").unwrap();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "logs": [{ "level": "warn", "message": "disk 91% full", "source": "nightly-backup" }]
+        })))
+        .with_body(r#"{"success":true,"count":1}"#)
+        .create();
+
+    let checkpoint = dir.join("checkpoint.json");
+    let child = Command::new(env!("CARGO_BIN_EXE_timberlogs"))
+        .args([
+            "agent",
+            "--from-start",
+            "--poll-ms",
+            "50",
+            "--level",
+            "warn",
+            "--text",
+        ])
+        .arg(&path)
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .env("TIMBERLOGS_API_KEY", "tb_cli_key")
+        .env("TIMBERLOGS_BASE_URL", server.url())
+        .env("TIMBERLOGS_SOURCE", "nightly-backup")
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    mock.assert();
+    assert!(std::fs::read_to_string(&checkpoint)
+        .unwrap()
+        .contains("\"offset\":14"));
+}
+
+#[test]
+fn test_agent_exits_with_error_when_api_key_is_refused() {
+    let dir = std::env::temp_dir().join(format!("timberlogs-cli-auth-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.log");
+    std::fs::write(&path, "started\n").unwrap();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .with_status(401)
+        .with_body("invalid API key")
+        .expect_at_least(1)
+        .create();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_timberlogs"))
+        .args(["agent", "--from-start", "--poll-ms", "50", "--text"])
+        .arg(&path)
+        .arg("--checkpoint")
+        .arg(dir.join("checkpoint.json"))
+        .env("TIMBERLOGS_API_KEY", "tb_revoked")
+        .env("TIMBERLOGS_BASE_URL", server.url())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("timberlogs agent: HTTP error 401: invalid API key"),
+        "{stderr}"
+    );
+    mock.assert();
+    std::fs::remove_dir_all(&dir).ok();
+}
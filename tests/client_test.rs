@@ -1,10 +1,16 @@
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use std::time::Duration;
 
 use timberlogs::{
-    Environment, IngestRawOptions, LogEntry, LogLevel, RawFormat, RetryConfig, TimberlogsClient,
-    TimberlogsConfig, TimberlogsError,
+    idempotency_key, read_dead_letters, ApiLimits, BoxFuture, CallbackDeadLetterSink,
+    CircuitBreakerConfig, CircuitState, CreateLogArgs, Environment, Executor, FlowResponse,
+    InMemoryTransport, IngestRawOptions, IngestResponse, JsonlDeadLetterSink, LevelFileConfig,
+    LogEntry, LogLevel, RateLimit, RawFormat, RetryConfig, SamplingConfig, ShutdownReport,
+    TimberlogsClient, TimberlogsConfig, TimberlogsError, TraceContext, Transport,
+    LATENCY_BUCKETS_MS,
 };
 
 fn test_config(api_key: &str) -> TimberlogsConfig {
@@ -30,11 +36,31 @@ fn mock_config(api_key: &str, base_url: &str) -> TimberlogsConfig {
     }
 }
 
+// Records every log sent to /v1/logs so tests can inspect the batches.
+async fn capture_logs(server: &mut mockito::ServerGuard) -> Arc<Mutex<Vec<serde_json::Value>>> {
+    let captured = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&captured);
+    server
+        .mock("POST", "/v1/logs")
+        .with_status(200)
+        .with_body_from_request(move |req| {
+            let body: serde_json::Value = serde_json::from_slice(req.body().unwrap()).unwrap();
+            let logs = body["logs"].as_array().unwrap().clone();
+            let count = logs.len();
+            sink.lock().unwrap().extend(logs);
+            format!(r#"{{"success":true,"count":{count}}}"#).into()
+        })
+        .create_async()
+        .await;
+    captured
+}
+
 // ── LogEntry defaults ──
 
 #[tokio::test]
 async fn test_log_entry_defaults() {
     let entry = LogEntry::default();
+    assert!(entry.id.is_none());
     assert_eq!(entry.level, LogLevel::Info);
     assert!(entry.message.is_empty());
     assert!(entry.data.is_none());
@@ -50,11 +76,17 @@ async fn test_log_entry_defaults() {
     assert!(entry.timestamp.is_none());
     assert!(entry.ip_address.is_none());
     assert!(entry.country.is_none());
+    assert!(entry.trace_id.is_none());
+    assert!(entry.span_id.is_none());
+    assert!(entry.parent_span_id.is_none());
+    assert!(entry.trace_flags.is_none());
+    assert!(entry.target.is_none());
 }
 
 #[tokio::test]
 async fn test_log_entry_with_all_fields() {
     let entry = LogEntry {
+        id: Some("log_1".into()),
         level: LogLevel::Error,
         message: "test error".into(),
         data: Some(HashMap::from([("key".into(), serde_json::json!("value"))])),
@@ -70,6 +102,11 @@ async fn test_log_entry_with_all_fields() {
         timestamp: Some(1700000000000),
         ip_address: Some("192.168.1.1".into()),
         country: Some("US".into()),
+        trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".into()),
+        span_id: Some("00f067aa0ba902b7".into()),
+        parent_span_id: Some("53995c3f42cd8ad8".into()),
+        trace_flags: Some(1),
+        target: Some("my_app::db".into()),
     };
 
     assert_eq!(entry.level, LogLevel::Error);
@@ -89,10 +126,20 @@ async fn test_config_defaults() {
     assert!(config.api_key.is_empty());
     assert!(config.version.is_none());
     assert!(config.batch_size.is_none());
+    assert!(config.max_batch_bytes.is_none());
     assert!(config.flush_interval_ms.is_none());
     assert!(config.min_level.is_none());
+    assert!(config.level_overrides.is_none());
+    assert!(config.level_file.is_none());
+    assert!(config.legacy_levels.is_none());
     assert!(config.retry.is_none());
+    assert!(config.circuit_breaker.is_none());
     assert!(config.on_error.is_none());
+    assert!(config.dead_letter.is_none());
+    assert!(config.flush_on_drop.is_none());
+    assert!(config.sampling.is_none());
+    assert!(config.transport.is_none());
+    assert!(config.executor.is_none());
     assert!(config.base_url.is_none());
 }
 
@@ -129,6 +176,159 @@ async fn test_min_level_allows_higher_levels() {
     client.disconnect().await.ok();
 }
 
+// ── Runtime level changes ──
+
+#[tokio::test]
+async fn test_set_min_level_at_runtime() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        min_level: Some(LogLevel::Warn),
+        batch_size: Some(100),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    client.info("before", None).await.unwrap();
+    client.set_min_level(LogLevel::Info).await;
+    assert_eq!(client.min_level().await, LogLevel::Info);
+    client.info("after", None).await.unwrap();
+    client.disconnect().await.unwrap();
+
+    let logs = captured.lock().unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["message"], "after");
+}
+
+#[tokio::test]
+async fn test_level_override_by_target_prefix() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        min_level: Some(LogLevel::Warn),
+        level_overrides: Some(HashMap::from([("my_app::db".into(), LogLevel::Debug)])),
+        batch_size: Some(100),
+        ..mock_config("tb_key", &server.url())
+    });
+    client
+        .set_level_override("my_app", Some(LogLevel::Error))
+        .await;
+
+    for target in ["my_app::db::pool", "my_app::http", "other"] {
+        client
+            .log(LogEntry {
+                level: LogLevel::Debug,
+                message: format!("debug from {target}"),
+                target: Some(target.into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+    client.disconnect().await.unwrap();
+
+    let logs = captured.lock().unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["message"], "debug from my_app::db::pool");
+    assert!(logs[0].get("target").is_none());
+}
+
+fn level_file_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("timberlogs-{name}-{}.levels", std::process::id()))
+}
+
+#[tokio::test]
+async fn test_level_file_loaded_and_reloaded() {
+    let path = level_file_path("reload");
+    std::fs::write(&path, "# incident overrides\nerror\ntest=warn").unwrap();
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        level_file: Some(LevelFileConfig {
+            path: path.clone(),
+            ..Default::default()
+        }),
+        ..test_config("tb_test_key")
+    });
+    assert_eq!(client.min_level().await, LogLevel::Error);
+
+    std::fs::write(&path, "info").unwrap();
+    client.reload_level_file().await.unwrap();
+    assert_eq!(client.min_level().await, LogLevel::Info);
+
+    std::fs::write(&path, "verbose").unwrap();
+    let err = client.reload_level_file().await.unwrap_err().to_string();
+    assert!(err.contains("unknown log level: verbose"), "{err}");
+
+    std::fs::remove_file(&path).ok();
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_runtime_levels_survive_level_file_reload() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+    let path = level_file_path("runtime");
+    std::fs::write(&path, "error\nmy_app::db=warn").unwrap();
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        level_file: Some(LevelFileConfig {
+            path: path.clone(),
+            ..Default::default()
+        }),
+        batch_size: Some(100),
+        ..mock_config("tb_key", &server.url())
+    });
+    client.set_min_level(LogLevel::Debug).await;
+    client.set_level_override("my_app::db", None).await;
+
+    std::fs::write(&path, "warn\nmy_app::db=error\nmy_app::http=info").unwrap();
+    client.reload_level_file().await.unwrap();
+    assert_eq!(client.min_level().await, LogLevel::Debug);
+
+    for target in ["my_app::db", "my_app::http", "other"] {
+        client
+            .log(LogEntry {
+                level: LogLevel::Debug,
+                message: format!("debug from {target}"),
+                target: Some(target.into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+    client.disconnect().await.unwrap();
+    std::fs::remove_file(&path).ok();
+
+    let logs = captured.lock().unwrap();
+    let messages: Vec<_> = logs.iter().map(|l| l["message"].clone()).collect();
+    assert_eq!(messages, ["debug from my_app::db", "debug from other"]);
+}
+
+#[tokio::test]
+async fn test_level_file_polling_picks_up_changes() {
+    let path = level_file_path("poll");
+    std::fs::write(&path, "warn").unwrap();
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        level_file: Some(LevelFileConfig {
+            path: path.clone(),
+            poll_interval_ms: Some(20),
+            ..Default::default()
+        }),
+        ..test_config("tb_test_key")
+    });
+    assert_eq!(client.min_level().await, LogLevel::Warn);
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    std::fs::write(&path, "debug, test=error").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    assert_eq!(client.min_level().await, LogLevel::Debug);
+
+    std::fs::remove_file(&path).ok();
+    client.disconnect().await.unwrap();
+}
+
 // ── Validation: empty message ──
 
 #[tokio::test]
@@ -143,7 +343,10 @@ async fn test_validation_empty_message() {
         .await;
 
     assert!(result.is_err());
-    assert!(result.unwrap_err().to_string().contains("message must not be empty"));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("message must not be empty"));
 }
 
 // ── Validation: message too long ──
@@ -160,7 +363,10 @@ async fn test_validation_message_too_long() {
         .await;
 
     assert!(result.is_err());
-    assert!(result.unwrap_err().to_string().contains("message exceeds 10000"));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("message exceeds 10000"));
 }
 
 #[tokio::test]
@@ -212,7 +418,10 @@ async fn test_validation_tag_too_long() {
         .await;
 
     assert!(result.is_err());
-    assert!(result.unwrap_err().to_string().contains("tags[0] exceeds 50"));
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("tags[0] exceeds 50"));
 }
 
 // ── Validation: step_index ──
@@ -447,170 +656,1194 @@ async fn test_disconnect_flushes() {
         ..mock_config("tb_key", &server.url())
     });
 
-    client.info("will be flushed on disconnect", None).await.unwrap();
+    client
+        .info("will be flushed on disconnect", None)
+        .await
+        .unwrap();
     client.disconnect().await.unwrap();
 
     mock.assert_async().await;
 }
 
-// ── HTTP error handling ──
-
-#[tokio::test]
-async fn test_http_error_returns_error() {
-    let mut server = mockito::Server::new_async().await;
-    let _mock = server
-        .mock("POST", "/v1/logs")
-        .with_status(500)
-        .with_body("Internal Server Error")
-        .create_async()
-        .await;
-
-    let client = TimberlogsClient::new(mock_config("tb_key", &server.url()));
-
-    // batch_size=1, so this triggers a flush that will fail
-    let result = client.info("test", None).await;
-    assert!(result.is_err());
-    let err = result.unwrap_err().to_string();
-    assert!(err.contains("500"));
+// Delays every batch, then records it or fails it.
+struct SlowTransport {
+    delay: Duration,
+    fail: bool,
+    recorded: InMemoryTransport,
 }
 
-// ── Retry logic ──
-
-#[tokio::test]
-async fn test_retry_succeeds_after_failure() {
-    let mut server = mockito::Server::new_async().await;
+#[async_trait::async_trait]
+impl Transport for SlowTransport {
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError> {
+        tokio::time::sleep(self.delay).await;
+        if self.fail {
+            return Err(TimberlogsError::Http {
+                status: 503,
+                body: String::new(),
+            });
+        }
+        self.recorded.send_batch(logs).await
+    }
 
-    // First request fails, second succeeds
-    let _fail = server
-        .mock("POST", "/v1/logs")
-        .with_status(500)
-        .with_body("error")
-        .expect(1)
-        .create_async()
-        .await;
+    async fn ingest_raw(
+        &self,
+        body: &str,
+        format: RawFormat,
+        options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError> {
+        self.recorded.ingest_raw(body, format, options).await
+    }
 
-    let _ok = server
-        .mock("POST", "/v1/logs")
-        .with_status(200)
-        .with_body(r#"{"success":true,"count":1}"#)
-        .expect(1)
-        .create_async()
-        .await;
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError> {
+        self.recorded.create_flow(name).await
+    }
+}
 
-    let mut client = TimberlogsClient::new(TimberlogsConfig {
+fn slow_config(transport: &Arc<SlowTransport>) -> TimberlogsConfig {
+    TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        flush_interval_ms: Some(20),
         retry: Some(RetryConfig {
-            max_retries: 1,
+            max_retries: 0,
             initial_delay_ms: 10,
             max_delay_ms: 10,
         }),
-        ..mock_config("tb_key", &server.url())
+        ..test_config("")
+    }
+}
+
+#[tokio::test]
+async fn test_disconnect_waits_for_in_flight_batch() {
+    let transport = Arc::new(SlowTransport {
+        delay: Duration::from_millis(100),
+        fail: false,
+        recorded: InMemoryTransport::new(),
     });
+    let mut client = TimberlogsClient::new(slow_config(&transport));
 
-    client.info("retry test", None).await.unwrap();
+    client.info("in flight", None).await.unwrap();
+    // Let the background flush pick the entry up and start sending it.
+    tokio::time::sleep(Duration::from_millis(40)).await;
     client.disconnect().await.unwrap();
-}
 
-// ── on_error callback ──
+    assert_eq!(transport.recorded.logs().len(), 1);
+}
 
 #[tokio::test]
-async fn test_on_error_callback() {
-    let mut server = mockito::Server::new_async().await;
-    let _mock = server
-        .mock("POST", "/v1/logs")
-        .with_status(500)
-        .with_body("error")
-        .create_async()
-        .await;
+async fn test_shutdown_reports_sent_and_rejects_new_entries() {
+    let transport = Arc::new(SlowTransport {
+        delay: Duration::from_millis(10),
+        fail: false,
+        recorded: InMemoryTransport::new(),
+    });
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        flush_interval_ms: Some(60000),
+        ..slow_config(&transport)
+    });
 
-    let error_count = Arc::new(AtomicU32::new(0));
-    let counter = Arc::clone(&error_count);
+    for i in 0..3 {
+        client.info(format!("entry {i}"), None).await.unwrap();
+    }
+    let report = client.shutdown(Duration::from_secs(1)).await;
 
-    let mut client = TimberlogsClient::new(TimberlogsConfig {
-        on_error: Some(Box::new(move |_err: &TimberlogsError| {
-            counter.fetch_add(1, Ordering::SeqCst);
+    assert_eq!(
+        report,
+        ShutdownReport {
+            sent: 3,
+            dropped: 0
+        }
+    );
+    assert_eq!(transport.recorded.logs().len(), 3);
+    assert!(matches!(
+        client.info("too late", None).await,
+        Err(TimberlogsError::NotConnected)
+    ));
+    assert!(client.flow("too late").await.is_err());
+}
+
+#[tokio::test]
+async fn test_shutdown_drops_entries_at_deadline() {
+    let transport = Arc::new(SlowTransport {
+        delay: Duration::from_millis(10),
+        fail: true,
+        recorded: InMemoryTransport::new(),
+    });
+    let errors = Arc::new(AtomicU32::new(0));
+    let errors_clone = Arc::clone(&errors);
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        flush_interval_ms: Some(60000),
+        on_error: Some(Box::new(move |_| {
+            errors_clone.fetch_add(1, Ordering::SeqCst);
         })),
-        batch_size: Some(100),
-        flush_interval_ms: Some(50), // short interval to trigger background flush
-        ..mock_config("tb_key", &server.url())
+        ..slow_config(&transport)
     });
 
-    client.info("queued", None).await.unwrap();
-
-    // Wait for background flush to fire
-    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-
-    assert!(error_count.load(Ordering::SeqCst) > 0, "on_error should have been called");
+    client.info("one", None).await.unwrap();
+    client.info("two", None).await.unwrap();
+    let started = std::time::Instant::now();
+    let report = client.shutdown(Duration::from_millis(150)).await;
 
-    client.disconnect().await.ok();
+    assert_eq!(
+        report,
+        ShutdownReport {
+            sent: 0,
+            dropped: 2
+        }
+    );
+    assert!(started.elapsed() < Duration::from_millis(500));
+    // The drain keeps retrying until the deadline.
+    assert!(errors.load(Ordering::SeqCst) > 1);
 }
 
-// ── ingest_raw ──
-
 #[tokio::test]
-async fn test_ingest_raw_json() {
-    let mut server = mockito::Server::new_async().await;
-    let mock = server
-        .mock("POST", "/v1/logs")
-        .match_query(mockito::Matcher::AllOf(vec![
-            mockito::Matcher::UrlEncoded("format".into(), "json".into()),
-        ]))
-        .match_header("content-type", "application/json")
-        .with_status(200)
-        .with_body("")
-        .expect(1)
-        .create_async()
-        .await;
+async fn test_shutdown_stops_flushing_when_in_flight_batch_outlasts_deadline() {
+    let transport = Arc::new(SlowTransport {
+        delay: Duration::from_millis(200),
+        fail: false,
+        recorded: InMemoryTransport::new(),
+    });
+    let client = TimberlogsClient::new(slow_config(&transport));
 
-    let mut client = TimberlogsClient::new(mock_config("tb_key", &server.url()));
+    client.info("in flight", None).await.unwrap();
+    // Let the background flush pick the entry up and start sending it.
+    tokio::time::sleep(Duration::from_millis(40)).await;
+    client.info("queued", None).await.unwrap();
+    let report = client.shutdown(Duration::from_millis(50)).await;
+    assert_eq!(
+        report,
+        ShutdownReport {
+            sent: 0,
+            dropped: 1
+        }
+    );
 
-    client
-        .ingest_raw(r#"{"msg":"hello"}"#, RawFormat::Json, None)
-        .await
-        .unwrap();
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let sent: Vec<String> = transport
+        .recorded
+        .logs()
+        .into_iter()
+        .map(|log| log.message)
+        .collect();
+    assert!(!sent.contains(&"queued".to_string()), "{sent:?}");
+    assert_eq!(client.stats().await.dropped, 1);
+}
 
-    client.disconnect().await.unwrap();
-    mock.assert_async().await;
+#[test]
+fn test_flush_on_drop_outside_runtime_does_not_panic() {
+    let transport = Arc::new(SlowTransport {
+        delay: Duration::ZERO,
+        fail: false,
+        recorded: InMemoryTransport::new(),
+    });
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = runtime.block_on(async {
+        let client = TimberlogsClient::new(TimberlogsConfig {
+            flush_interval_ms: Some(60000),
+            flush_on_drop: Some(true),
+            ..slow_config(&transport)
+        });
+        client.info("never sent", None).await.unwrap();
+        client
+    });
+    drop(runtime);
+
+    drop(client);
+    assert!(transport.recorded.logs().is_empty());
 }
 
 #[tokio::test]
-async fn test_ingest_raw_csv_with_options() {
-    let mut server = mockito::Server::new_async().await;
-    let mock = server
-        .mock("POST", "/v1/logs")
-        .match_query(mockito::Matcher::AllOf(vec![
-            mockito::Matcher::UrlEncoded("format".into(), "csv".into()),
-            mockito::Matcher::UrlEncoded("source".into(), "my-app".into()),
-            mockito::Matcher::UrlEncoded("dataset".into(), "logs".into()),
-        ]))
-        .match_header("content-type", "text/csv")
-        .with_status(200)
-        .with_body("")
-        .expect(1)
-        .create_async()
-        .await;
+async fn test_flush_on_drop() {
+    let transport = Arc::new(SlowTransport {
+        delay: Duration::from_millis(10),
+        fail: false,
+        recorded: InMemoryTransport::new(),
+    });
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        flush_interval_ms: Some(60000),
+        flush_on_drop: Some(true),
+        ..slow_config(&transport)
+    });
+    client.info("flushed after drop", None).await.unwrap();
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(transport.recorded.logs().len(), 1);
 
-    let mut client = TimberlogsClient::new(mock_config("tb_key", &server.url()));
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        flush_interval_ms: Some(60000),
+        ..slow_config(&transport)
+    });
+    client.info("lost", None).await.unwrap();
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(transport.recorded.logs().len(), 1);
+}
 
-    client
-        .ingest_raw(
-            "level,message\ninfo,hello",
-            RawFormat::Csv,
-            Some(IngestRawOptions {
-                source: Some("my-app".into()),
-                dataset: Some("logs".into()),
-                ..Default::default()
-            }),
-        )
-        .await
-        .unwrap();
+// ── Batch byte limits ──
+
+#[tokio::test]
+async fn test_max_batch_bytes_splits_requests() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        max_batch_bytes: Some(1000),
+        ..test_config("")
+    });
 
+    for i in 0..6 {
+        client
+            .info(format!("{i}{}", "x".repeat(300)), None)
+            .await
+            .unwrap();
+    }
     client.disconnect().await.unwrap();
-    mock.assert_async().await;
+
+    let batches = transport.batches();
+    assert!(batches.len() > 1);
+    for batch in &batches {
+        let body = serde_json::to_vec(&serde_json::json!({ "logs": batch })).unwrap();
+        assert!(body.len() <= 1000, "batch of {} bytes", body.len());
+    }
+    let order: Vec<String> = transport
+        .logs()
+        .iter()
+        .map(|log| log.message[..1].to_string())
+        .collect();
+    assert_eq!(order, ["0", "1", "2", "3", "4", "5"]);
 }
 
-#[tokio::test]
-async fn test_ingest_raw_all_formats() {
-    let formats = vec![
+// Rejects requests with more than `max_logs` entries the way a body size
+// limit would.
+struct SizeLimitedTransport {
+    max_logs: usize,
+    requests: AtomicU32,
+    recorded: InMemoryTransport,
+}
+
+#[async_trait::async_trait]
+impl Transport for SizeLimitedTransport {
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        if logs.len() > self.max_logs {
+            return Err(TimberlogsError::Http {
+                status: 413,
+                body: "payload too large".into(),
+            });
+        }
+        self.recorded.send_batch(logs).await
+    }
+
+    async fn ingest_raw(
+        &self,
+        body: &str,
+        format: RawFormat,
+        options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError> {
+        self.recorded.ingest_raw(body, format, options).await
+    }
+
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError> {
+        self.recorded.create_flow(name).await
+    }
+}
+
+#[tokio::test]
+async fn test_payload_too_large_bisects_batch() {
+    let transport = Arc::new(SizeLimitedTransport {
+        max_logs: 3,
+        requests: AtomicU32::new(0),
+        recorded: InMemoryTransport::new(),
+    });
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        ..test_config("")
+    });
+
+    for i in 0..8 {
+        client.info(format!("log {i}"), None).await.unwrap();
+    }
+    client.disconnect().await.unwrap();
+
+    let sizes: Vec<usize> = transport.recorded.batches().iter().map(Vec::len).collect();
+    assert_eq!(sizes, [2, 2, 2, 2]);
+    let messages: Vec<String> = transport
+        .recorded
+        .logs()
+        .into_iter()
+        .map(|log| log.message)
+        .collect();
+    let expected: Vec<String> = (0..8).map(|i| format!("log {i}")).collect();
+    assert_eq!(messages, expected);
+}
+
+#[tokio::test]
+async fn test_payload_too_large_single_entry_is_dead_lettered() {
+    let transport = Arc::new(SizeLimitedTransport {
+        max_logs: 0,
+        requests: AtomicU32::new(0),
+        recorded: InMemoryTransport::new(),
+    });
+    let dead = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&dead);
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        retry: Some(RetryConfig {
+            max_retries: 3,
+            initial_delay_ms: 10,
+            max_delay_ms: 10,
+        }),
+        dead_letter: Some(Arc::new(CallbackDeadLetterSink::new(move |log, e| {
+            sink.lock()
+                .unwrap()
+                .push((log.message.clone(), e.to_string()));
+        }))),
+        ..test_config("")
+    });
+
+    client.info("too big", None).await.unwrap();
+    client.flush().await.unwrap();
+    client.flush().await.unwrap();
+
+    // Not retried, and not requeued either.
+    assert_eq!(transport.requests.load(Ordering::SeqCst), 1);
+    let dead = dead.lock().unwrap();
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].0, "too big");
+    assert!(dead[0].1.contains("413"));
+}
+
+#[tokio::test]
+#[should_panic(expected = "max_batch_bytes must be greater than 0")]
+async fn test_max_batch_bytes_zero_panics() {
+    TimberlogsClient::new(TimberlogsConfig {
+        max_batch_bytes: Some(0),
+        ..test_config("")
+    });
+}
+
+// ── Partial batch rejections ──
+
+// Answers 400 for any batch containing a message that starts with "bad".
+struct PickyTransport {
+    requests: AtomicU32,
+    recorded: InMemoryTransport,
+}
+
+#[async_trait::async_trait]
+impl Transport for PickyTransport {
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        if logs.iter().any(|log| log.message.starts_with("bad")) {
+            return Err(TimberlogsError::Http {
+                status: 400,
+                body: "invalid entry".into(),
+            });
+        }
+        self.recorded.send_batch(logs).await
+    }
+
+    async fn ingest_raw(
+        &self,
+        body: &str,
+        format: RawFormat,
+        options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError> {
+        self.recorded.ingest_raw(body, format, options).await
+    }
+
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError> {
+        self.recorded.create_flow(name).await
+    }
+}
+
+#[tokio::test]
+async fn test_bad_request_bisects_and_dead_letters_invalid_entry() {
+    let transport = Arc::new(PickyTransport {
+        requests: AtomicU32::new(0),
+        recorded: InMemoryTransport::new(),
+    });
+    let dead = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&dead);
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        dead_letter: Some(Arc::new(CallbackDeadLetterSink::new(move |log, _| {
+            sink.lock().unwrap().push(log.message.clone());
+        }))),
+        ..test_config("")
+    });
+
+    for message in ["a", "b", "bad c", "d", "e"] {
+        client.info(message, None).await.unwrap();
+    }
+    client.flush().await.unwrap();
+
+    let sent: Vec<String> = transport
+        .recorded
+        .logs()
+        .into_iter()
+        .map(|log| log.message)
+        .collect();
+    assert_eq!(sent, ["a", "b", "d", "e"]);
+    assert_eq!(*dead.lock().unwrap(), ["bad c"]);
+
+    // Nothing was requeued.
+    let requests = transport.requests.load(Ordering::SeqCst);
+    client.flush().await.unwrap();
+    assert_eq!(transport.requests.load(Ordering::SeqCst), requests);
+}
+
+#[tokio::test]
+async fn test_rejected_entries_in_response_are_dead_lettered() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .with_status(200)
+        .with_body(
+            r#"{"success":true,"count":1,"rejected":[{"index":1,"error":"dataset unknown"}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let dead = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&dead);
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        dead_letter: Some(Arc::new(CallbackDeadLetterSink::new(move |log, e| {
+            sink.lock()
+                .unwrap()
+                .push((log.message.clone(), e.to_string()));
+        }))),
+        ..mock_config("tb_test_key", &server.url())
+    });
+
+    client.info("kept", None).await.unwrap();
+    client.info("refused", None).await.unwrap();
+    client.flush().await.unwrap();
+    client.flush().await.unwrap();
+
+    mock.assert_async().await;
+    let dead = dead.lock().unwrap();
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].0, "refused");
+    assert!(dead[0].1.contains("dataset unknown"));
+}
+
+#[tokio::test]
+async fn test_rejections_reported_without_dead_letter_hook() {
+    let transport = Arc::new(PickyTransport {
+        requests: AtomicU32::new(0),
+        recorded: InMemoryTransport::new(),
+    });
+    let errors = Arc::new(AtomicU32::new(0));
+    let counter = Arc::clone(&errors);
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        on_error: Some(Box::new(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })),
+        ..test_config("")
+    });
+
+    client.info("bad entry", None).await.unwrap();
+    client.flush().await.unwrap();
+
+    assert_eq!(errors.load(Ordering::SeqCst), 1);
+    assert!(transport.recorded.logs().is_empty());
+}
+
+#[tokio::test]
+async fn test_success_without_response_body_is_not_retried() {
+    let mut server = mockito::Server::new_async().await;
+    for body in ["", "OK"] {
+        let mock = server
+            .mock("POST", "/v1/logs")
+            .with_status(200)
+            .with_body(body)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = TimberlogsClient::new(TimberlogsConfig {
+            batch_size: Some(100),
+            ..mock_config("tb_key", &server.url())
+        });
+        client.info("accepted", None).await.unwrap();
+        client.flush().await.unwrap();
+        client.flush().await.unwrap();
+
+        mock.assert_async().await;
+        let stats = client.stats().await;
+        assert_eq!((stats.sent, stats.requeued), (1, 0), "body {body:?}");
+        mock.remove_async().await;
+    }
+}
+
+// ── Dead-letter sink ──
+
+fn dead_letter_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("timberlogs-{name}-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[tokio::test]
+async fn test_exhausted_retries_go_to_dead_letter_file_and_resubmit() {
+    let path = dead_letter_path("exhausted");
+    let failing = Arc::new(SlowTransport {
+        delay: Duration::ZERO,
+        fail: true,
+        recorded: InMemoryTransport::new(),
+    });
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        dead_letter: Some(Arc::new(JsonlDeadLetterSink::new(&path).unwrap())),
+        flush_interval_ms: Some(60000),
+        ..slow_config(&failing)
+    });
+
+    client.info("first", None).await.unwrap();
+    client.info("second", None).await.unwrap();
+    assert!(matches!(
+        client.flush().await,
+        Err(TimberlogsError::Http { status: 503, .. })
+    ));
+    // Dead-lettered rather than requeued.
+    client.flush().await.unwrap();
+
+    let letters = read_dead_letters(&path).unwrap();
+    assert_eq!(letters.len(), 2);
+    assert_eq!(letters[0].log.message, "first");
+    assert!(letters[0].error.contains("503"));
+    assert!(letters[0].failed_at > 0);
+
+    let transport = Arc::new(InMemoryTransport::new());
+    let retry_client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        ..test_config("")
+    });
+    assert_eq!(retry_client.resubmit_dead_letters(&path).await.unwrap(), 2);
+    let messages: Vec<String> = transport
+        .logs()
+        .into_iter()
+        .map(|log| log.message)
+        .collect();
+    assert_eq!(messages, ["first", "second"]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_dead_letters_unsent_entries() {
+    let failing = Arc::new(SlowTransport {
+        delay: Duration::ZERO,
+        fail: true,
+        recorded: InMemoryTransport::new(),
+    });
+    let dead = Arc::new(AtomicU32::new(0));
+    let counter = Arc::clone(&dead);
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        dead_letter: Some(Arc::new(CallbackDeadLetterSink::new(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }))),
+        ..slow_config(&failing)
+    });
+
+    client.info("a", None).await.unwrap();
+    client.info("b", None).await.unwrap();
+    let report = client.shutdown(Duration::from_millis(50)).await;
+
+    assert_eq!(
+        report,
+        ShutdownReport {
+            sent: 0,
+            dropped: 2
+        }
+    );
+    assert_eq!(dead.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_read_dead_letters_reports_bad_line() {
+    let path = dead_letter_path("bad-line");
+    std::fs::write(&path, "\nnot json\n").unwrap();
+
+    let err = read_dead_letters(&path).unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{err}");
+    std::fs::remove_file(&path).unwrap();
+}
+
+// ── Circuit breaker ──
+
+// Fails every request with a 503 while `down` is set.
+struct OutageTransport {
+    down: AtomicBool,
+    requests: AtomicU32,
+    recorded: InMemoryTransport,
+}
+
+impl OutageTransport {
+    fn new() -> Self {
+        Self {
+            down: AtomicBool::new(true),
+            requests: AtomicU32::new(0),
+            recorded: InMemoryTransport::new(),
+        }
+    }
+
+    fn check(&self) -> Result<(), TimberlogsError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        if self.down.load(Ordering::SeqCst) {
+            return Err(TimberlogsError::Http {
+                status: 503,
+                body: String::new(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for OutageTransport {
+    async fn send_batch(&self, logs: &[CreateLogArgs]) -> Result<IngestResponse, TimberlogsError> {
+        self.check()?;
+        self.recorded.send_batch(logs).await
+    }
+
+    async fn ingest_raw(
+        &self,
+        body: &str,
+        format: RawFormat,
+        options: &IngestRawOptions,
+    ) -> Result<(), TimberlogsError> {
+        self.check()?;
+        self.recorded.ingest_raw(body, format, options).await
+    }
+
+    async fn create_flow(&self, name: &str) -> Result<FlowResponse, TimberlogsError> {
+        self.check()?;
+        self.recorded.create_flow(name).await
+    }
+}
+
+fn breaker_config(
+    transport: &Arc<OutageTransport>,
+    changes: &Arc<Mutex<Vec<(CircuitState, CircuitState)>>>,
+) -> TimberlogsConfig {
+    let changes = Arc::clone(changes);
+    TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        retry: Some(RetryConfig {
+            max_retries: 5,
+            initial_delay_ms: 1,
+            max_delay_ms: 1,
+        }),
+        circuit_breaker: Some(CircuitBreakerConfig {
+            failure_threshold: 3,
+            open_duration_ms: 50,
+            on_state_change: Some(Box::new(move |from, to| {
+                changes.lock().unwrap().push((from, to));
+            })),
+            ..Default::default()
+        }),
+        ..test_config("")
+    }
+}
+
+#[tokio::test]
+async fn test_circuit_opens_spools_and_recovers() {
+    let transport = Arc::new(OutageTransport::new());
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        // Spooled entries stay queued even with a sink.
+        dead_letter: Some(Arc::new(CallbackDeadLetterSink::new(|_, _| {
+            panic!("nothing should be dead-lettered");
+        }))),
+        ..breaker_config(&transport, &changes)
+    });
+
+    client.info("during outage", None).await.unwrap();
+    let err = client.flush().await.unwrap_err();
+    assert!(matches!(err, TimberlogsError::CircuitOpen));
+    // The breaker cut the retries short after three failures.
+    assert_eq!(transport.requests.load(Ordering::SeqCst), 3);
+    assert_eq!(client.circuit_state(), CircuitState::Open);
+
+    // Fails fast while open, without touching the transport.
+    assert!(matches!(
+        client.flush().await,
+        Err(TimberlogsError::CircuitOpen)
+    ));
+    assert!(matches!(
+        client.flow("checkout").await,
+        Err(TimberlogsError::CircuitOpen)
+    ));
+    assert!(matches!(
+        client.ingest_raw("line", RawFormat::Text, None).await,
+        Err(TimberlogsError::CircuitOpen)
+    ));
+    assert_eq!(transport.requests.load(Ordering::SeqCst), 3);
+
+    transport.down.store(false, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    client.flush().await.unwrap();
+
+    assert_eq!(transport.recorded.logs()[0].message, "during outage");
+    assert_eq!(client.circuit_state(), CircuitState::Closed);
+    assert_eq!(
+        *changes.lock().unwrap(),
+        [
+            (CircuitState::Closed, CircuitState::Open),
+            (CircuitState::Open, CircuitState::HalfOpen),
+            (CircuitState::HalfOpen, CircuitState::Closed),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_failed_trial_reopens_circuit() {
+    let transport = Arc::new(OutageTransport::new());
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let client = TimberlogsClient::new(breaker_config(&transport, &changes));
+
+    client.info("a", None).await.unwrap();
+    assert!(client.flush().await.is_err());
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(client.flush().await.is_err());
+
+    // One trial request, then open again.
+    assert_eq!(transport.requests.load(Ordering::SeqCst), 4);
+    assert_eq!(client.circuit_state(), CircuitState::Open);
+    assert_eq!(
+        changes.lock().unwrap().last(),
+        Some(&(CircuitState::HalfOpen, CircuitState::Open))
+    );
+}
+
+#[tokio::test]
+async fn test_rejections_do_not_trip_circuit() {
+    let transport = Arc::new(PickyTransport {
+        requests: AtomicU32::new(0),
+        recorded: InMemoryTransport::new(),
+    });
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        circuit_breaker: Some(CircuitBreakerConfig {
+            failure_threshold: 1,
+            ..Default::default()
+        }),
+        ..test_config("")
+    });
+
+    client.info("bad one", None).await.unwrap();
+    client.info("bad two", None).await.unwrap();
+    client.flush().await.unwrap();
+
+    assert_eq!(client.circuit_state(), CircuitState::Closed);
+}
+
+// ── Stats ──
+
+#[tokio::test]
+async fn test_stats_track_the_pipeline() {
+    let transport = Arc::new(OutageTransport::new());
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        min_level: Some(LogLevel::Info),
+        retry: Some(RetryConfig {
+            max_retries: 1,
+            initial_delay_ms: 1,
+            max_delay_ms: 1,
+        }),
+        ..test_config("")
+    });
+
+    client.debug("below min level", None).await.unwrap();
+    assert!(client.warn("", None).await.is_err());
+    client.info("one", None).await.unwrap();
+    client.info("two", None).await.unwrap();
+    assert!(client.flush().await.is_err());
+
+    let stats = client.stats().await;
+    assert_eq!(stats.filtered, 1);
+    assert_eq!(stats.validation_failures, 1);
+    assert_eq!(stats.enqueued, 2);
+    assert_eq!(stats.retried, 1);
+    assert_eq!(stats.requeued, 2);
+    assert_eq!(stats.queue_depth, 2);
+    assert_eq!(stats.sent, 0);
+
+    transport.down.store(false, Ordering::SeqCst);
+    client.flush().await.unwrap();
+    transport.down.store(true, Ordering::SeqCst);
+    client.info("lost", None).await.unwrap();
+    client.shutdown(Duration::from_millis(20)).await;
+
+    let stats = client.stats().await;
+    assert_eq!(stats.sent, 2);
+    assert_eq!(stats.dropped, 1);
+    assert_eq!(stats.queue_depth, 0);
+    let latency = &stats.batch_latency;
+    assert!(latency.count >= 3);
+    assert_eq!(latency.buckets.len(), LATENCY_BUCKETS_MS.len() + 1);
+    assert_eq!(latency.buckets.iter().sum::<u64>(), latency.count);
+}
+
+#[tokio::test]
+async fn test_stats_count_filtered_flow_steps_and_rejections() {
+    let transport = Arc::new(PickyTransport {
+        requests: AtomicU32::new(0),
+        recorded: InMemoryTransport::new(),
+    });
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        min_level: Some(LogLevel::Info),
+        ..test_config("")
+    });
+
+    let mut flow = client.flow("job").await.unwrap();
+    flow.debug("skipped", None).await.unwrap();
+    flow.info("kept", None).await.unwrap();
+    client.info("bad entry", None).await.unwrap();
+    client.flush().await.unwrap();
+
+    let stats = client.stats().await;
+    assert_eq!(stats.filtered, 1);
+    assert_eq!(stats.sent, 1);
+    assert_eq!(stats.dropped, 1);
+    assert_eq!(stats.retried, 0);
+}
+
+// ── HTTP error handling ──
+
+#[tokio::test]
+async fn test_http_error_returns_error() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/v1/logs")
+        .with_status(500)
+        .with_body("Internal Server Error")
+        .create_async()
+        .await;
+
+    let client = TimberlogsClient::new(mock_config("tb_key", &server.url()));
+
+    // batch_size=1, so this triggers a flush that will fail
+    let result = client.info("test", None).await;
+    assert!(result.is_err());
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("500"));
+}
+
+// ── Retry logic ──
+
+#[tokio::test]
+async fn test_retry_succeeds_after_failure() {
+    let mut server = mockito::Server::new_async().await;
+
+    // First request fails, second succeeds
+    let _fail = server
+        .mock("POST", "/v1/logs")
+        .with_status(500)
+        .with_body("error")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let _ok = server
+        .mock("POST", "/v1/logs")
+        .with_status(200)
+        .with_body(r#"{"success":true,"count":1}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        retry: Some(RetryConfig {
+            max_retries: 1,
+            initial_delay_ms: 10,
+            max_delay_ms: 10,
+        }),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    client.info("retry test", None).await.unwrap();
+    client.disconnect().await.unwrap();
+}
+
+// ── Idempotency ──
+
+#[tokio::test]
+async fn test_idempotency_key_and_entry_ids_stable_across_requeue() {
+    let mut server = mockito::Server::new_async().await;
+    let seen = Arc::new(Mutex::new(Vec::new()));
+
+    for status in [500, 200] {
+        let sink = Arc::clone(&seen);
+        server
+            .mock("POST", "/v1/logs")
+            .with_status(status)
+            .with_body_from_request(move |req| {
+                let key = req.header("idempotency-key")[0]
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                let body: serde_json::Value = serde_json::from_slice(req.body().unwrap()).unwrap();
+                let ids: Vec<String> = body["logs"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|log| log["id"].as_str().unwrap().to_string())
+                    .collect();
+                sink.lock().unwrap().push((key, ids));
+                r#"{"success":true,"count":2}"#.into()
+            })
+            .expect(1)
+            .create_async()
+            .await;
+    }
+
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        ..mock_config("tb_key", &server.url())
+    });
+    client.info("one", None).await.unwrap();
+    client.info("two", None).await.unwrap();
+    assert!(client.flush().await.is_err());
+    client.flush().await.unwrap();
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0], seen[1]);
+    let (key, ids) = &seen[0];
+    assert_eq!(key.len(), 36);
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+}
+
+#[tokio::test]
+async fn test_idempotency_key_depends_on_entries() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        ..test_config("")
+    });
+    client.info("same message", None).await.unwrap();
+    client.info("same message", None).await.unwrap();
+
+    let batches = transport.batches();
+    assert_eq!(idempotency_key(&batches[0]), idempotency_key(&batches[0]));
+    assert_ne!(idempotency_key(&batches[0]), idempotency_key(&batches[1]));
+}
+
+#[tokio::test]
+async fn test_entry_id_is_kept_when_set() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        ..test_config("")
+    });
+    client
+        .log(LogEntry {
+            id: Some("line-42".into()),
+            message: "stable".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    client.info("random", None).await.unwrap();
+
+    let logs = transport.logs();
+    assert_eq!(logs[0].id.as_deref(), Some("line-42"));
+    assert_eq!(logs[1].id.as_ref().unwrap().len(), 36);
+}
+
+// ── Verify ──
+
+#[tokio::test]
+async fn test_verify_reports_limits_and_clock_skew() {
+    let mut server = mockito::Server::new_async().await;
+    let server_time = std::time::SystemTime::now() + Duration::from_secs(120);
+    let mock = server
+        .mock("GET", "/v1/verify")
+        .match_header("X-API-Key", "tb_test_key")
+        .with_status(200)
+        .with_header("Date", &httpdate::fmt_http_date(server_time))
+        .with_body(
+            r#"{"limits":{"maxBatchSize":500,"maxBatchBytes":1000000,"requestsPerMinute":600}}"#,
+        )
+        .create_async()
+        .await;
+
+    let client = TimberlogsClient::new(mock_config("tb_test_key", &server.url()));
+    let report = client.verify().await.unwrap();
+    mock.assert_async().await;
+
+    assert!(report.authenticated);
+    assert_eq!(report.endpoint, format!("{}/v1/verify", server.url()));
+    assert_eq!(report.limits.max_batch_size, Some(500));
+    assert_eq!(report.limits.max_batch_bytes, Some(1_000_000));
+    assert_eq!(report.limits.requests_per_minute, Some(600));
+    // The Date header drops sub-second precision.
+    let skew = report.clock_skew_ms.unwrap();
+    assert!((118_000..=121_000).contains(&skew), "skew was {skew}");
+}
+
+#[tokio::test]
+async fn test_verify_reports_rejected_key_without_error() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", "/v1/verify")
+        .with_status(401)
+        .with_body(r#"{"error":"invalid API key"}"#)
+        .create_async()
+        .await;
+
+    let client = TimberlogsClient::new(mock_config("tb_wrong", &server.url()));
+    let report = client.verify().await.unwrap();
+    mock.assert_async().await;
+
+    assert!(!report.authenticated);
+    assert_eq!(report.limits, ApiLimits::default());
+}
+
+#[tokio::test]
+async fn test_verify_fails_on_server_error() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", "/v1/verify")
+        .with_status(503)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        retry: Some(RetryConfig {
+            max_retries: 3,
+            initial_delay_ms: 10,
+            max_delay_ms: 10,
+        }),
+        ..mock_config("tb_test_key", &server.url())
+    });
+    let result = client.verify().await;
+    assert!(matches!(
+        result,
+        Err(TimberlogsError::Http { status: 503, .. })
+    ));
+    // Not retried, so a readiness probe fails fast.
+    mock.assert_async().await;
+}
+
+// ── on_error callback ──
+
+#[tokio::test]
+async fn test_on_error_callback() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/v1/logs")
+        .with_status(500)
+        .with_body("error")
+        .create_async()
+        .await;
+
+    let error_count = Arc::new(AtomicU32::new(0));
+    let counter = Arc::clone(&error_count);
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        on_error: Some(Box::new(move |_err: &TimberlogsError| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })),
+        batch_size: Some(100),
+        flush_interval_ms: Some(50), // short interval to trigger background flush
+        ..mock_config("tb_key", &server.url())
+    });
+
+    client.info("queued", None).await.unwrap();
+
+    // Wait for background flush to fire
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+
+    assert!(
+        error_count.load(Ordering::SeqCst) > 0,
+        "on_error should have been called"
+    );
+
+    client.disconnect().await.ok();
+}
+
+// ── ingest_raw ──
+
+#[tokio::test]
+async fn test_ingest_raw_json() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .match_query(mockito::Matcher::AllOf(vec![mockito::Matcher::UrlEncoded(
+            "format".into(),
+            "json".into(),
+        )]))
+        .match_header("content-type", "application/json")
+        .with_status(200)
+        .with_body("")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut client = TimberlogsClient::new(mock_config("tb_key", &server.url()));
+
+    client
+        .ingest_raw(r#"{"msg":"hello"}"#, RawFormat::Json, None)
+        .await
+        .unwrap();
+
+    client.disconnect().await.unwrap();
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_ingest_raw_csv_with_options() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("format".into(), "csv".into()),
+            mockito::Matcher::UrlEncoded("source".into(), "my-app".into()),
+            mockito::Matcher::UrlEncoded("dataset".into(), "logs".into()),
+        ]))
+        .match_header("content-type", "text/csv")
+        .with_status(200)
+        .with_body("")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut client = TimberlogsClient::new(mock_config("tb_key", &server.url()));
+
+    client
+        .ingest_raw(
+            "level,message\ninfo,hello",
+            RawFormat::Csv,
+            Some(IngestRawOptions {
+                source: Some("my-app".into()),
+                dataset: Some("logs".into()),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+    client.disconnect().await.unwrap();
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_ingest_raw_all_formats() {
+    let formats = vec![
         (RawFormat::Json, "json", "application/json"),
         (RawFormat::Jsonl, "jsonl", "application/x-ndjson"),
         (RawFormat::Syslog, "syslog", "application/x-syslog"),
@@ -665,9 +1898,221 @@ async fn test_log_entry_serialization_includes_set_fields() {
 
 #[tokio::test]
 async fn test_log_level_ordering() {
+    assert!(LogLevel::Trace < LogLevel::Debug);
     assert!(LogLevel::Debug < LogLevel::Info);
     assert!(LogLevel::Info < LogLevel::Warn);
     assert!(LogLevel::Warn < LogLevel::Error);
+    assert!(LogLevel::Error < LogLevel::Fatal);
+}
+
+#[tokio::test]
+async fn test_log_level_from_str_and_display() {
+    assert_eq!("trace".parse::<LogLevel>().unwrap(), LogLevel::Trace);
+    assert_eq!(" WARNING ".parse::<LogLevel>().unwrap(), LogLevel::Warn);
+    assert_eq!("Critical".parse::<LogLevel>().unwrap(), LogLevel::Fatal);
+    assert_eq!(LogLevel::Fatal.to_string(), "fatal");
+    assert_eq!(LogLevel::Warn.to_string(), "warn");
+
+    let err = "loud".parse::<LogLevel>().unwrap_err();
+    assert!(matches!(err, TimberlogsError::Validation(_)));
+    assert!(err.to_string().contains("unknown log level: loud"));
+}
+
+#[tokio::test]
+async fn test_raw_format_from_str() {
+    assert_eq!("CSV".parse::<RawFormat>().unwrap(), RawFormat::Csv);
+    assert_eq!("ndjson".parse::<RawFormat>().unwrap(), RawFormat::Jsonl);
+    assert_eq!(" syslog ".parse::<RawFormat>().unwrap(), RawFormat::Syslog);
+    let err = "xml".parse::<RawFormat>().unwrap_err();
+    assert!(err.to_string().contains("unknown raw format: xml"));
+}
+
+#[tokio::test]
+async fn test_environment_from_str_and_display() {
+    assert_eq!(
+        "Production".parse::<Environment>().unwrap(),
+        Environment::Production
+    );
+    assert_eq!(
+        "prod".parse::<Environment>().unwrap(),
+        Environment::Production
+    );
+    assert_eq!(
+        "dev".parse::<Environment>().unwrap(),
+        Environment::Development
+    );
+    assert_eq!(
+        "staging".parse::<Environment>().unwrap(),
+        Environment::Staging
+    );
+    assert_eq!(Environment::Staging.to_string(), "staging");
+    assert!("not valid!".parse::<Environment>().is_err());
+}
+
+// ── Custom environments ──
+
+#[tokio::test]
+async fn test_custom_environment_parsing() {
+    assert_eq!(
+        "qa".parse::<Environment>().unwrap(),
+        Environment::Custom("qa".into())
+    );
+    assert_eq!(
+        "Preview-123".parse::<Environment>().unwrap(),
+        Environment::Custom("preview-123".into())
+    );
+    assert_eq!(Environment::custom("canary").unwrap().to_string(), "canary");
+    assert_eq!(
+        Environment::custom("staging").unwrap(),
+        Environment::Staging
+    );
+
+    assert!(Environment::custom("").is_err());
+    assert!(Environment::custom("Canary").is_err());
+    assert!(Environment::custom("pr 42").is_err());
+    let err = Environment::custom("x".repeat(51)).unwrap_err().to_string();
+    assert!(err.contains("1-50 characters"), "{err}");
+}
+
+#[tokio::test]
+async fn test_custom_environment_serialization() {
+    assert_eq!(
+        serde_json::to_string(&Environment::Custom("canary".into())).unwrap(),
+        "\"canary\""
+    );
+    assert_eq!(
+        serde_json::from_str::<Environment>("\"production\"").unwrap(),
+        Environment::Production
+    );
+    assert_eq!(
+        serde_json::from_str::<Environment>("\"qa\"").unwrap(),
+        Environment::Custom("qa".into())
+    );
+    assert!(serde_json::from_str::<Environment>("\"no spaces\"").is_err());
+}
+
+#[tokio::test]
+async fn test_custom_environment_sent_with_logs() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        environment: Environment::Custom("preview-42".into()),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    client.info("deployed", None).await.unwrap();
+    client.disconnect().await.unwrap();
+
+    assert_eq!(captured.lock().unwrap()[0]["environment"], "preview-42");
+}
+
+#[tokio::test]
+async fn test_ingest_raw_custom_environment() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "environment".into(),
+            "canary".into(),
+        ))
+        .with_status(200)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = TimberlogsClient::new(mock_config("tb_key", &server.url()));
+
+    client
+        .ingest_raw(
+            "line",
+            RawFormat::Text,
+            Some(IngestRawOptions {
+                environment: Some(Environment::Custom("canary".into())),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+    let result = client
+        .ingest_raw(
+            "line",
+            RawFormat::Text,
+            Some(IngestRawOptions {
+                environment: Some(Environment::Custom("Not Valid".into())),
+                ..Default::default()
+            }),
+        )
+        .await;
+    assert!(matches!(result, Err(TimberlogsError::Validation(_))));
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+#[should_panic(expected = "environment may only contain")]
+async fn test_invalid_custom_environment_panics() {
+    TimberlogsClient::new(TimberlogsConfig {
+        environment: Environment::Custom("QA env".into()),
+        ..test_config("tb_test_key")
+    });
+}
+
+#[tokio::test]
+async fn test_try_new_rejects_invalid_config() {
+    let result = TimberlogsClient::try_new(TimberlogsConfig {
+        environment: Environment::Custom("QA env".into()),
+        ..test_config("tb_test_key")
+    });
+    let err = result.err().unwrap();
+    assert!(matches!(err, TimberlogsError::Validation(_)));
+    assert!(
+        err.to_string().contains("environment may only contain"),
+        "{err}"
+    );
+
+    let result = TimberlogsClient::try_new(TimberlogsConfig {
+        batch_size: Some(0),
+        ..test_config("tb_test_key")
+    });
+    assert!(matches!(result, Err(TimberlogsError::Validation(_))));
+
+    for (failure_threshold, success_threshold) in [(0, 1), (5, 0)] {
+        let result = TimberlogsClient::try_new(TimberlogsConfig {
+            circuit_breaker: Some(CircuitBreakerConfig {
+                failure_threshold,
+                success_threshold,
+                ..Default::default()
+            }),
+            ..test_config("tb_test_key")
+        });
+        let err = result.err().unwrap().to_string();
+        assert!(err.contains("threshold must be greater than 0"), "{err}");
+    }
+}
+
+#[test]
+fn test_try_new_outside_runtime_returns_error() {
+    let result = TimberlogsClient::try_new(test_config("tb_test_key"));
+    let err = result.err().unwrap().to_string();
+    assert!(err.contains("inside a tokio runtime"), "{err}");
+}
+
+#[tokio::test]
+async fn test_custom_environment_matching_builtin_is_normalized() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::try_new(TimberlogsConfig {
+        environment: Environment::Custom("production".into()),
+        ..mock_config("tb_key", &server.url())
+    })
+    .unwrap();
+    client.info("deployed", None).await.unwrap();
+    client.disconnect().await.unwrap();
+
+    assert_eq!(captured.lock().unwrap()[0]["environment"], "production");
 }
 
 // ── Environment serialization ──
@@ -779,3 +2224,525 @@ async fn test_flow_step_index_skips_filtered_levels() {
     // Verify step_index is 2 (only incremented for warn + error)
     assert_eq!(flow.step_index(), 2);
 }
+
+// ── Sampling and rate limiting ──
+
+#[tokio::test]
+async fn test_sampling_rate_zero_drops_level() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        sampling: Some(SamplingConfig {
+            rates: HashMap::from([(LogLevel::Debug, 0.0), (LogLevel::Error, 1.0)]),
+            ..Default::default()
+        }),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    for _ in 0..5 {
+        client.debug("noisy", None).await.unwrap();
+    }
+    client.error("kept", None).await.unwrap();
+    client.disconnect().await.unwrap();
+    assert_eq!(client.stats().await.dropped, 5);
+
+    let logs = captured.lock().unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["message"], "kept");
+}
+
+#[tokio::test]
+async fn test_rate_limit_caps_level_at_burst() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        sampling: Some(SamplingConfig {
+            rate_limits: HashMap::from([(
+                LogLevel::Info,
+                RateLimit {
+                    per_second: 0.001,
+                    burst: 2,
+                },
+            )]),
+            ..Default::default()
+        }),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    for i in 0..5 {
+        client.info(format!("info {i}"), None).await.unwrap();
+    }
+    client.warn("unlimited", None).await.unwrap();
+    client.disconnect().await.unwrap();
+    assert_eq!(client.stats().await.dropped, 3);
+
+    let logs = captured.lock().unwrap();
+    let messages: Vec<_> = logs
+        .iter()
+        .map(|l| l["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(messages, vec!["info 0", "info 1", "unlimited"]);
+}
+
+#[tokio::test]
+async fn test_throttle_collapses_repeated_messages() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        sampling: Some(SamplingConfig {
+            throttle_window_ms: Some(60_000),
+            ..Default::default()
+        }),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    for i in 0..5 {
+        client
+            .warn(
+                format!("disk {}% full on vol-{:x}", 90 + i, 0xbeef0 + i),
+                None,
+            )
+            .await
+            .unwrap();
+    }
+    client.info("other", None).await.unwrap();
+    client.disconnect().await.unwrap();
+    assert_eq!(client.stats().await.dropped, 0);
+
+    let logs = captured.lock().unwrap();
+    assert_eq!(logs.len(), 3);
+    assert_eq!(logs[0]["message"], "disk 90% full on vol-beef0");
+    assert!(logs[0].get("suppressedCount").is_none());
+    assert_eq!(logs[1]["message"], "other");
+    assert_eq!(logs[2]["message"], "disk 94% full on vol-beef4");
+    assert_eq!(logs[2]["suppressedCount"], 4);
+}
+
+#[tokio::test]
+async fn test_throttle_summaries_are_counted_as_enqueued() {
+    let mut server = mockito::Server::new_async().await;
+    let _captured = capture_logs(&mut server).await;
+
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        sampling: Some(SamplingConfig {
+            throttle_window_ms: Some(60_000),
+            ..Default::default()
+        }),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    for _ in 0..5 {
+        client.warn("disk full", None).await.unwrap();
+    }
+    client.flush().await.unwrap();
+
+    let stats = client.stats().await;
+    assert_eq!(stats.sent, 2);
+    assert_eq!(
+        stats.enqueued,
+        stats.sent + stats.dropped + stats.queue_depth as u64
+    );
+}
+
+#[tokio::test]
+#[should_panic(expected = "sample rate for Debug must be between 0 and 1")]
+async fn test_sampling_rate_out_of_range_panics() {
+    TimberlogsClient::new(TimberlogsConfig {
+        sampling: Some(SamplingConfig {
+            rates: HashMap::from([(LogLevel::Debug, 1.5)]),
+            ..Default::default()
+        }),
+        ..test_config("tb_test_key")
+    });
+}
+
+fn rate_limited_config(per_second: f64, burst: u32) -> TimberlogsConfig {
+    TimberlogsConfig {
+        sampling: Some(SamplingConfig {
+            rate_limits: HashMap::from([(LogLevel::Info, RateLimit { per_second, burst })]),
+            ..Default::default()
+        }),
+        ..test_config("tb_test_key")
+    }
+}
+
+#[tokio::test]
+async fn test_rate_limit_with_zero_burst_is_rejected() {
+    let result = TimberlogsClient::try_new(rate_limited_config(10.0, 0));
+    let err = result.err().unwrap();
+    assert!(matches!(err, TimberlogsError::Validation(_)));
+    assert!(err.to_string().contains("burst for Info"), "{err}");
+}
+
+#[tokio::test]
+async fn test_rate_limit_with_invalid_per_second_is_rejected() {
+    for per_second in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let result = TimberlogsClient::try_new(rate_limited_config(per_second, 5));
+        let err = result.err().unwrap();
+        assert!(matches!(err, TimberlogsError::Validation(_)));
+        assert!(
+            err.to_string().contains("per second"),
+            "{per_second}: {err}"
+        );
+    }
+}
+
+// ── Trace and Fatal levels ──
+
+#[tokio::test]
+async fn test_trace_and_fatal_sent_natively() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    client.trace("t", None).await.unwrap();
+    client.fatal("f", None).await.unwrap();
+    client.disconnect().await.unwrap();
+
+    let logs = captured.lock().unwrap();
+    assert_eq!(logs[0]["level"], "trace");
+    assert_eq!(logs[1]["level"], "fatal");
+    assert!(logs[1].get("data").is_none());
+}
+
+#[tokio::test]
+async fn test_legacy_levels_map_trace_and_fatal() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        legacy_levels: Some(true),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    client.trace("t", None).await.unwrap();
+    client
+        .fatal(
+            "f",
+            Some(HashMap::from([("code".into(), serde_json::json!(7))])),
+        )
+        .await
+        .unwrap();
+    client.warn("w", None).await.unwrap();
+    client.disconnect().await.unwrap();
+
+    let logs = captured.lock().unwrap();
+    assert_eq!(logs[0]["level"], "debug");
+    assert_eq!(logs[0]["data"]["originalLevel"], "trace");
+    assert_eq!(logs[1]["level"], "error");
+    assert_eq!(logs[1]["data"]["originalLevel"], "fatal");
+    assert_eq!(logs[1]["data"]["code"], 7);
+    assert_eq!(logs[2]["level"], "warn");
+    assert!(logs[2].get("data").is_none());
+}
+
+#[tokio::test]
+async fn test_ingest_raw_legacy_level_query() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("level".into(), "error".into()),
+            mockito::Matcher::UrlEncoded("environment".into(), "production".into()),
+        ]))
+        .with_status(200)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        legacy_levels: Some(true),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    client
+        .ingest_raw(
+            "boom",
+            RawFormat::Text,
+            Some(IngestRawOptions {
+                level: Some(LogLevel::Fatal),
+                environment: Some(Environment::Production),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+}
+
+// ── Trace context ──
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+#[tokio::test]
+async fn test_traceparent_round_trip() {
+    let ctx = TraceContext::from_traceparent(TRACEPARENT).unwrap();
+    assert_eq!(ctx.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(ctx.span_id, "00f067aa0ba902b7");
+    assert_eq!(ctx.trace_flags, 1);
+    assert!(ctx.is_sampled());
+    assert_eq!(ctx.to_traceparent(), TRACEPARENT);
+
+    let child = ctx.child();
+    assert_eq!(child.trace_id, ctx.trace_id);
+    assert_eq!(child.parent_span_id.as_deref(), Some("00f067aa0ba902b7"));
+    assert_ne!(child.span_id, ctx.span_id);
+    assert_eq!(child.span_id.len(), 16);
+
+    let root = TraceContext::new_root();
+    assert_eq!(
+        TraceContext::from_traceparent(&root.to_traceparent()).unwrap(),
+        root
+    );
+}
+
+#[tokio::test]
+async fn test_traceparent_rejects_invalid_headers() {
+    for header in [
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+    ] {
+        assert!(
+            TraceContext::from_traceparent(header).is_err(),
+            "{header:?}"
+        );
+    }
+    // Future versions may append fields
+    assert!(TraceContext::from_traceparent(
+        "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra"
+    )
+    .is_ok());
+}
+
+#[tokio::test]
+async fn test_tracestate_round_trip() {
+    let ctx = TraceContext::from_traceparent(TRACEPARENT)
+        .unwrap()
+        .with_tracestate("rojo=00f067aa0ba902b7, congo=t61rcWkgMzE,")
+        .unwrap();
+    assert_eq!(
+        ctx.trace_state,
+        vec![
+            ("rojo".to_string(), "00f067aa0ba902b7".to_string()),
+            ("congo".to_string(), "t61rcWkgMzE".to_string()),
+        ]
+    );
+    assert_eq!(
+        ctx.to_tracestate().as_deref(),
+        Some("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE")
+    );
+    assert!(TraceContext::new_root().to_tracestate().is_none());
+    assert!(TraceContext::new_root().with_tracestate("novalue").is_err());
+}
+
+#[tokio::test]
+async fn test_logs_inherit_current_trace_context() {
+    let mut server = mockito::Server::new_async().await;
+    let captured = capture_logs(&mut server).await;
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(100),
+        ..mock_config("tb_key", &server.url())
+    });
+
+    let ctx = TraceContext::from_traceparent(TRACEPARENT).unwrap().child();
+    let span_id = ctx.span_id.clone();
+    ctx.scope(async {
+        client.info("inside span", None).await.unwrap();
+        client
+            .log(LogEntry {
+                message: "explicit trace".into(),
+                trace_id: Some("0af7651916cd43dd8448eb211c80319c".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    })
+    .await;
+    client.info("outside span", None).await.unwrap();
+    client.disconnect().await.unwrap();
+
+    let logs = captured.lock().unwrap();
+    assert_eq!(logs[0]["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(logs[0]["spanId"], span_id.as_str());
+    assert_eq!(logs[0]["parentSpanId"], "00f067aa0ba902b7");
+    assert_eq!(logs[0]["traceFlags"], 1);
+    assert_eq!(logs[1]["traceId"], "0af7651916cd43dd8448eb211c80319c");
+    assert!(logs[1].get("spanId").is_none());
+    assert!(logs[2].get("traceId").is_none());
+}
+
+#[tokio::test]
+async fn test_validation_trace_ids() {
+    let client = TimberlogsClient::new(test_config("tb_test_key"));
+
+    let result = client
+        .log(LogEntry {
+            message: "test".into(),
+            span_id: Some("not-hex".into()),
+            ..Default::default()
+        })
+        .await;
+
+    let err = result.unwrap_err().to_string();
+    assert!(
+        err.contains("span_id must be 16 lowercase hex characters"),
+        "{err}"
+    );
+}
+
+// ── Pluggable transport ──
+
+#[tokio::test]
+async fn test_in_memory_transport_records_batches() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(2),
+        legacy_levels: Some(true),
+        ..test_config("")
+    });
+
+    client.warn("disk almost full", None).await.unwrap();
+    client.fatal("disk full", None).await.unwrap();
+    client.info("cleanup started", None).await.unwrap();
+    client.disconnect().await.unwrap();
+
+    let batches = transport.batches();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0].len(), 2);
+
+    let logs = transport.logs();
+    assert_eq!(logs[0].level, LogLevel::Warn);
+    assert_eq!(logs[0].message, "disk almost full");
+    assert_eq!(logs[0].source, "test");
+    assert_eq!(logs[1].level, LogLevel::Error);
+    assert_eq!(logs[1].data.as_ref().unwrap()["originalLevel"], "fatal");
+    assert_eq!(logs[2].message, "cleanup started");
+
+    transport.clear();
+    assert!(transport.logs().is_empty());
+}
+
+#[tokio::test]
+async fn test_in_memory_transport_flows_and_raw_ingest() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        ..test_config("")
+    });
+
+    let mut flow = client.flow("checkout").await.unwrap();
+    assert_eq!(flow.id, "checkout-0");
+    flow.info("started", None).await.unwrap();
+    drop(flow);
+
+    client
+        .ingest_raw(
+            "a\nb",
+            RawFormat::Text,
+            Some(IngestRawOptions {
+                source: Some("importer".into()),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+    client.disconnect().await.unwrap();
+
+    assert_eq!(transport.flows()[0].name, "checkout");
+    let logs = transport.logs();
+    assert_eq!(logs[0].flow_id.as_deref(), Some("checkout-0"));
+    assert_eq!(logs[0].step_index, Some(0));
+
+    let raw = transport.raw_ingests();
+    assert_eq!(raw.len(), 1);
+    assert_eq!(raw[0].body, "a\nb");
+    assert_eq!(raw[0].format, RawFormat::Text);
+    assert_eq!(raw[0].options.source.as_deref(), Some("importer"));
+}
+
+#[tokio::test]
+async fn test_custom_transport_flushes_without_api_key() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        batch_size: Some(100),
+        flush_interval_ms: Some(20),
+        ..test_config("")
+    });
+
+    client.info("background flush", None).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    assert_eq!(transport.logs().len(), 1);
+}
+
+// ── Custom executor ──
+
+struct CountingExecutor {
+    spawned: AtomicU32,
+    sleeps: AtomicU32,
+}
+
+impl Executor for CountingExecutor {
+    fn spawn(&self, future: BoxFuture<()>) {
+        self.spawned.fetch_add(1, Ordering::SeqCst);
+        tokio::spawn(future);
+    }
+
+    fn sleep(&self, duration: std::time::Duration) -> BoxFuture<()> {
+        self.sleeps.fetch_add(1, Ordering::SeqCst);
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[tokio::test]
+async fn test_custom_executor_runs_background_tasks_and_retries() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .with_status(500)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let executor = Arc::new(CountingExecutor {
+        spawned: AtomicU32::new(0),
+        sleeps: AtomicU32::new(0),
+    });
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        executor: Some(executor.clone()),
+        retry: Some(RetryConfig {
+            max_retries: 1,
+            initial_delay_ms: 10,
+            max_delay_ms: 10,
+        }),
+        ..mock_config("tb_test_key", &server.url())
+    });
+    assert_eq!(executor.spawned.load(Ordering::SeqCst), 1);
+
+    let result = client.info("retried", None).await;
+    assert!(result.is_err());
+    mock.assert_async().await;
+    // One flush-timer sleep plus one retry backoff.
+    assert_eq!(executor.sleeps.load(Ordering::SeqCst), 2);
+}
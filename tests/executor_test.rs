@@ -0,0 +1,93 @@
+#![cfg(any(feature = "smol", feature = "async-std"))]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use timberlogs::{Environment, Executor, InMemoryTransport, TimberlogsClient, TimberlogsConfig};
+
+fn config(transport: &Arc<InMemoryTransport>, executor: Arc<dyn Executor>) -> TimberlogsConfig {
+    TimberlogsConfig {
+        source: "test".into(),
+        environment: Environment::Development,
+        batch_size: Some(100),
+        flush_interval_ms: Some(20),
+        transport: Some(transport.clone()),
+        executor: Some(executor),
+        ..Default::default()
+    }
+}
+
+#[cfg(feature = "smol")]
+#[test]
+fn test_smol_executor_flushes_in_background() {
+    smol::block_on(async {
+        let transport = Arc::new(InMemoryTransport::new());
+        let mut client =
+            TimberlogsClient::new(config(&transport, Arc::new(timberlogs::SmolExecutor)));
+
+        client.info("from smol", None).await.unwrap();
+        smol::Timer::after(Duration::from_millis(100)).await;
+        assert_eq!(transport.logs()[0].message, "from smol");
+
+        client.warn("on disconnect", None).await.unwrap();
+        client.disconnect().await.unwrap();
+        assert_eq!(transport.logs().len(), 2);
+    });
+}
+
+#[cfg(feature = "async-std")]
+#[test]
+fn test_async_std_executor_flushes_in_background() {
+    async_std::task::block_on(async {
+        let transport = Arc::new(InMemoryTransport::new());
+        let client =
+            TimberlogsClient::new(config(&transport, Arc::new(timberlogs::AsyncStdExecutor)));
+
+        client.info("from async-std", None).await.unwrap();
+        async_std::task::sleep(Duration::from_millis(100)).await;
+        assert_eq!(transport.logs()[0].message, "from async-std");
+    });
+}
+
+// The default transport runs on reqwest, which needs a tokio reactor that
+// neither smol nor async-std provides.
+#[cfg(feature = "smol")]
+#[test]
+fn test_smol_executor_with_default_http_transport() {
+    let mut server = mockito::Server::new();
+    let logs = server
+        .mock("POST", "/v1/logs")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({ "logs": [{ "message": "over http" }] }),
+        ))
+        .with_body(r#"{"success":true,"count":1}"#)
+        .create();
+    let query = server
+        .mock("GET", "/v1/logs")
+        .with_body(r#"{"logs":[]}"#)
+        .create();
+
+    smol::block_on(async {
+        let client = TimberlogsClient::new(TimberlogsConfig {
+            source: "test".into(),
+            environment: Environment::Development,
+            api_key: "tb_key".into(),
+            base_url: Some(server.url()),
+            executor: Some(Arc::new(timberlogs::SmolExecutor)),
+            ..Default::default()
+        });
+        client.info("over http", None).await.unwrap();
+        client.flush().await.unwrap();
+
+        let reader = timberlogs::QueryClient::new(timberlogs::QueryConfig {
+            api_key: "tb_key".into(),
+            base_url: Some(server.url()),
+            executor: Some(Arc::new(timberlogs::SmolExecutor)),
+        })
+        .unwrap();
+        let page = reader.query(&Default::default()).await.unwrap();
+        assert!(page.logs.is_empty());
+    });
+    logs.assert();
+    query.assert();
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "metrics")]
+
+use std::sync::Arc;
+
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use timberlogs::{Environment, InMemoryTransport, LogLevel, TimberlogsClient, TimberlogsConfig};
+
+#[tokio::test]
+async fn test_metrics_recorder_receives_counters() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().unwrap();
+
+    let transport = Arc::new(InMemoryTransport::new());
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        source: "metrics-test".into(),
+        environment: Environment::Development,
+        min_level: Some(LogLevel::Info),
+        batch_size: Some(100),
+        flush_interval_ms: Some(60000),
+        transport: Some(transport),
+        ..Default::default()
+    });
+    client.debug("filtered", None).await.unwrap();
+    client.info("one", None).await.unwrap();
+    client.info("two", None).await.unwrap();
+    client.flush().await.unwrap();
+
+    let metrics: Vec<(String, DebugValue)> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let key = key.key();
+            assert!(key
+                .labels()
+                .any(|label| label.key() == "source" && label.value() == "metrics-test"));
+            (key.name().to_string(), value)
+        })
+        .collect();
+    let get = |name: &str| metrics.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+
+    assert_eq!(
+        get("timberlogs_enqueued_total"),
+        Some(&DebugValue::Counter(2))
+    );
+    assert_eq!(
+        get("timberlogs_filtered_total"),
+        Some(&DebugValue::Counter(1))
+    );
+    assert_eq!(get("timberlogs_sent_total"), Some(&DebugValue::Counter(2)));
+    assert!(
+        matches!(get("timberlogs_batch_duration_seconds"), Some(DebugValue::Histogram(v)) if v.len() == 1)
+    );
+    assert!(matches!(get("timberlogs_queue_depth"), Some(DebugValue::Gauge(v)) if v.0 == 0.0));
+}
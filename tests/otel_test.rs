@@ -0,0 +1,336 @@
+#![cfg(all(feature = "opentelemetry", feature = "otlp"))]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity};
+use opentelemetry::{SpanId, TraceFlags, TraceId};
+use timberlogs::{
+    Environment, InMemoryTransport, OtlpConfig, OtlpProtocol, RawFormat, RetryConfig,
+    TimberlogsClient, TimberlogsConfig, TimberlogsError, TimberlogsLoggerProvider,
+};
+
+fn mock_config(base_url: &str) -> TimberlogsConfig {
+    TimberlogsConfig {
+        source: "test".into(),
+        environment: Environment::Staging,
+        api_key: "tb_key".into(),
+        version: Some("2.0.0".into()),
+        batch_size: Some(100),
+        flush_interval_ms: Some(60000),
+        base_url: Some(base_url.to_string()),
+        retry: Some(RetryConfig {
+            max_retries: 0,
+            initial_delay_ms: 10,
+            max_delay_ms: 10,
+        }),
+        ..Default::default()
+    }
+}
+
+fn otlp_config(endpoint: &str, protocol: OtlpProtocol) -> TimberlogsConfig {
+    TimberlogsConfig {
+        api_key: String::new(),
+        otlp: Some(OtlpConfig {
+            endpoint: endpoint.to_string(),
+            protocol,
+            headers: HashMap::from([("x-collector-token".into(), "secret".into())]),
+        }),
+        ..mock_config("http://127.0.0.1:9")
+    }
+}
+
+// Records raw request bodies posted to /v1/logs.
+async fn capture_bodies(
+    server: &mut mockito::ServerGuard,
+    content_type: &str,
+    response: &'static str,
+) -> Arc<Mutex<Vec<Vec<u8>>>> {
+    let captured = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&captured);
+    server
+        .mock("POST", "/v1/logs")
+        .match_header("content-type", content_type)
+        .with_status(200)
+        .with_body_from_request(move |req| {
+            sink.lock().unwrap().push(req.body().unwrap().clone());
+            response.into()
+        })
+        .create_async()
+        .await;
+    captured
+}
+
+// ── OpenTelemetry bridge ──
+
+#[tokio::test]
+async fn test_bridge_forwards_log_records() {
+    let mut server = mockito::Server::new_async().await;
+    let bodies = capture_bodies(
+        &mut server,
+        "application/json",
+        r#"{"success":true,"count":2}"#,
+    )
+    .await;
+
+    let client = Arc::new(TimberlogsClient::new(mock_config(&server.url())));
+    let provider = TimberlogsLoggerProvider::new(Arc::clone(&client));
+    let logger = provider.logger("checkout-service");
+
+    let mut record = logger.create_log_record();
+    record.set_severity_number(Severity::Warn2);
+    record.set_target("checkout::payments");
+    record.set_body("card declined".into());
+    record.add_attribute("exception.type", "CardError");
+    record.add_attribute("enduser.id", "user_42");
+    record.add_attribute("amount", 12.5);
+    record.set_trace_context(
+        TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+        SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+        Some(TraceFlags::SAMPLED),
+    );
+    logger.emit(record);
+
+    let mut record = logger.create_log_record();
+    record.set_severity_number(Severity::Fatal);
+    record.set_body(AnyValue::Int(7));
+    logger.emit(record);
+
+    provider.force_flush().await.unwrap();
+
+    let bodies = bodies.lock().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bodies[0]).unwrap();
+    let logs = body["logs"].as_array().unwrap();
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[0]["level"], "warn");
+    assert_eq!(logs[0]["message"], "card declined");
+    assert_eq!(logs[0]["errorName"], "CardError");
+    assert_eq!(logs[0]["userId"], "user_42");
+    assert_eq!(logs[0]["data"]["amount"], 12.5);
+    assert_eq!(logs[0]["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(logs[0]["spanId"], "00f067aa0ba902b7");
+    assert_eq!(logs[0]["traceFlags"], 1);
+    assert_eq!(logs[1]["level"], "fatal");
+    assert_eq!(logs[1]["message"], "7");
+}
+
+#[tokio::test]
+async fn test_bridge_respects_target_level_overrides() {
+    let mut server = mockito::Server::new_async().await;
+    let bodies = capture_bodies(
+        &mut server,
+        "application/json",
+        r#"{"success":true,"count":1}"#,
+    )
+    .await;
+
+    let client = Arc::new(TimberlogsClient::new(TimberlogsConfig {
+        level_overrides: Some(HashMap::from([(
+            "noisy".into(),
+            timberlogs::LogLevel::Error,
+        )])),
+        ..mock_config(&server.url())
+    }));
+    let provider = TimberlogsLoggerProvider::new(Arc::clone(&client));
+    let logger = provider.logger("app");
+
+    for target in ["noisy::loop", "quiet"] {
+        let mut record = logger.create_log_record();
+        record.set_severity_number(Severity::Info);
+        record.set_target(target);
+        record.set_body(format!("from {target}").into());
+        logger.emit(record);
+    }
+    provider.force_flush().await.unwrap();
+
+    let body: serde_json::Value = serde_json::from_slice(&bodies.lock().unwrap()[0]).unwrap();
+    let logs = body["logs"].as_array().unwrap();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["message"], "from quiet");
+}
+
+#[tokio::test]
+async fn test_bridge_drops_records_beyond_capacity() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let client = Arc::new(TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        ..mock_config("http://127.0.0.1:9")
+    }));
+    let provider = TimberlogsLoggerProvider::with_capacity(Arc::clone(&client), 2);
+    let logger = provider.logger("burst");
+
+    // The background task can't run until this test yields, so only the
+    // first two records fit in the bridge.
+    for i in 0..5 {
+        let mut record = logger.create_log_record();
+        record.set_body(format!("record {i}").into());
+        logger.emit(record);
+    }
+    provider.force_flush().await.unwrap();
+
+    let messages: Vec<_> = transport.logs().into_iter().map(|l| l.message).collect();
+    assert_eq!(messages, ["record 0", "record 1"]);
+    assert_eq!(client.stats().await.dropped, 3);
+}
+
+#[tokio::test]
+async fn test_bridge_with_zero_capacity_holds_one_record() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let client = Arc::new(TimberlogsClient::new(TimberlogsConfig {
+        transport: Some(transport.clone()),
+        ..mock_config("http://127.0.0.1:9")
+    }));
+    let provider = TimberlogsLoggerProvider::with_capacity(Arc::clone(&client), 0);
+    let logger = provider.logger("burst");
+
+    for i in 0..2 {
+        let mut record = logger.create_log_record();
+        record.set_body(format!("record {i}").into());
+        logger.emit(record);
+    }
+    provider.force_flush().await.unwrap();
+
+    let messages: Vec<_> = transport.logs().into_iter().map(|l| l.message).collect();
+    assert_eq!(messages, ["record 0"]);
+    assert_eq!(client.stats().await.dropped, 1);
+}
+
+// ── OTLP export ──
+
+#[tokio::test]
+async fn test_otlp_json_export() {
+    let mut server = mockito::Server::new_async().await;
+    let bodies = capture_bodies(&mut server, "application/json", "{}").await;
+
+    let mut client = TimberlogsClient::new(otlp_config(&server.url(), OtlpProtocol::Json));
+    client
+        .log(timberlogs::LogEntry {
+            level: timberlogs::LogLevel::Error,
+            message: "payment failed".into(),
+            data: Some(HashMap::from([("attempt".into(), serde_json::json!(3))])),
+            timestamp: Some(1700000000000),
+            trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".into()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    client.disconnect().await.unwrap();
+
+    let body: serde_json::Value = serde_json::from_slice(&bodies.lock().unwrap()[0]).unwrap();
+    let resource_logs = &body["resourceLogs"][0];
+    let resource_attrs = resource_logs["resource"]["attributes"].as_array().unwrap();
+    assert!(resource_attrs.contains(&serde_json::json!({
+        "key": "service.name", "value": { "stringValue": "test" }
+    })));
+    assert!(resource_attrs.contains(&serde_json::json!({
+        "key": "deployment.environment.name", "value": { "stringValue": "staging" }
+    })));
+
+    let scope_logs = &resource_logs["scopeLogs"][0];
+    assert_eq!(scope_logs["scope"]["name"], "timberlogs");
+    let record = &scope_logs["logRecords"][0];
+    assert_eq!(record["severityNumber"], 17);
+    assert_eq!(record["severityText"], "ERROR");
+    assert_eq!(record["timeUnixNano"], "1700000000000000000");
+    assert_eq!(record["body"]["stringValue"], "payment failed");
+    assert_eq!(record["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert!(record["attributes"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!({
+            "key": "attempt", "value": { "intValue": "3" }
+        })));
+}
+
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ExportLogsServiceRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub resource_logs: Vec<ResourceLogs>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ResourceLogs {
+        #[prost(message, repeated, tag = "2")]
+        pub scope_logs: Vec<ScopeLogs>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ScopeLogs {
+        #[prost(message, repeated, tag = "2")]
+        pub log_records: Vec<LogRecord>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct LogRecord {
+        #[prost(int32, tag = "2")]
+        pub severity_number: i32,
+        #[prost(message, optional, tag = "5")]
+        pub body: Option<AnyValue>,
+        #[prost(bytes = "vec", tag = "10")]
+        pub span_id: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AnyValue {
+        #[prost(string, tag = "1")]
+        pub string_value: String,
+    }
+}
+
+#[tokio::test]
+async fn test_otlp_protobuf_export() {
+    let mut server = mockito::Server::new_async().await;
+    let bodies = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&bodies);
+    let mock = server
+        .mock("POST", "/v1/logs")
+        .match_header("x-collector-token", "secret")
+        .match_header("content-type", "application/x-protobuf")
+        .with_status(200)
+        .with_body_from_request(move |req| {
+            sink.lock().unwrap().push(req.body().unwrap().clone());
+            Vec::new()
+        })
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut client = TimberlogsClient::new(otlp_config(&server.url(), OtlpProtocol::Protobuf));
+    client
+        .log(timberlogs::LogEntry {
+            level: timberlogs::LogLevel::Trace,
+            message: "entering".into(),
+            span_id: Some("00f067aa0ba902b7".into()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    client.disconnect().await.unwrap();
+    mock.assert_async().await;
+
+    let bodies = bodies.lock().unwrap();
+    let request: proto::ExportLogsServiceRequest = prost::Message::decode(&bodies[0][..]).unwrap();
+    let record = &request.resource_logs[0].scope_logs[0].log_records[0];
+    assert_eq!(record.severity_number, 1);
+    assert_eq!(record.body.as_ref().unwrap().string_value, "entering");
+    assert_eq!(
+        record.span_id,
+        vec![0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7]
+    );
+}
+
+#[tokio::test]
+async fn test_otlp_mode_flows_and_ingest_raw() {
+    let mut client = TimberlogsClient::new(otlp_config("http://127.0.0.1:9", OtlpProtocol::Json));
+
+    let flow = client.flow("nightly-import").await.unwrap();
+    assert!(flow.id.starts_with("nightly-import-"));
+    assert_eq!(flow.id.len(), "nightly-import-".len() + 8);
+    drop(flow);
+
+    let result = client.ingest_raw("line", RawFormat::Text, None).await;
+    assert!(matches!(result, Err(TimberlogsError::Validation(_))));
+
+    client.disconnect().await.unwrap();
+}
@@ -0,0 +1,351 @@
+use futures_util::{StreamExt, TryStreamExt};
+use mockito::Matcher;
+use timberlogs::{
+    CreateLogArgs, Environment, FlowQuery, LogLevel, LogQuery, LogRecord, QueryClient, QueryConfig,
+    TimberlogsError,
+};
+
+fn query_client(base_url: &str) -> QueryClient {
+    QueryClient::new(QueryConfig {
+        api_key: "tb_read_key".into(),
+        base_url: Some(base_url.to_string()),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn record(id: &str, message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "id": id,
+        "timestamp": 1_700_000_000_000u64,
+        "level": "error",
+        "message": message,
+        "source": "api",
+        "environment": "production",
+        "tags": ["billing"],
+    })
+}
+
+fn log(message: &str) -> CreateLogArgs {
+    CreateLogArgs {
+        id: None,
+        level: LogLevel::Info,
+        message: message.into(),
+        source: "api".into(),
+        environment: Environment::Production,
+        version: None,
+        user_id: None,
+        session_id: None,
+        request_id: None,
+        data: None,
+        error_name: None,
+        error_stack: None,
+        tags: None,
+        flow_id: None,
+        step_index: None,
+        dataset: None,
+        timestamp: None,
+        ip_address: None,
+        country: None,
+        trace_id: None,
+        span_id: None,
+        parent_span_id: None,
+        trace_flags: None,
+        suppressed_count: None,
+    }
+}
+
+#[tokio::test]
+async fn test_query_sends_filters_and_parses_page() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", "/v1/logs")
+        .match_header("X-API-Key", "tb_read_key")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("level".into(), "error,fatal".into()),
+            Matcher::UrlEncoded("environment".into(), "production".into()),
+            Matcher::UrlEncoded("userId".into(), "user_1".into()),
+            Matcher::UrlEncoded("tags".into(), "billing,eu".into()),
+            Matcher::UrlEncoded("from".into(), "1000".into()),
+            Matcher::UrlEncoded("q".into(), "card declined".into()),
+            Matcher::UrlEncoded("limit".into(), "2".into()),
+        ]))
+        .with_body(
+            serde_json::json!({ "logs": [record("log_1", "card declined")], "nextCursor": "c2" })
+                .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let page = query_client(&server.url())
+        .query(&LogQuery {
+            levels: vec![LogLevel::Error, LogLevel::Fatal],
+            environment: Some(Environment::Production),
+            user_id: Some("user_1".into()),
+            tags: vec!["billing".into(), "eu".into()],
+            from: Some(1000),
+            search: Some("card declined".into()),
+            limit: Some(2),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    mock.assert_async().await;
+
+    assert_eq!(page.next_cursor.as_deref(), Some("c2"));
+    let log = &page.logs[0];
+    assert_eq!(log.id, "log_1");
+    assert_eq!(log.level, LogLevel::Error);
+    assert_eq!(log.environment, Environment::Production);
+    assert_eq!(log.tags, vec!["billing"]);
+    assert_eq!(log.user_id, None);
+}
+
+#[tokio::test]
+async fn test_query_all_follows_cursors() {
+    let mut server = mockito::Server::new_async().await;
+    let first = server
+        .mock("GET", "/v1/logs")
+        .match_query(Matcher::Missing)
+        .with_body(
+            serde_json::json!({ "logs": [record("a", "one"), record("b", "two")], "nextCursor": "p2" })
+                .to_string(),
+        )
+        .create_async()
+        .await;
+    let second = server
+        .mock("GET", "/v1/logs")
+        .match_query(Matcher::UrlEncoded("cursor".into(), "p2".into()))
+        .with_body(serde_json::json!({ "logs": [record("c", "three")] }).to_string())
+        .create_async()
+        .await;
+
+    let client = query_client(&server.url());
+    let logs: Vec<LogRecord> = client
+        .query_all(LogQuery::default())
+        .try_collect()
+        .await
+        .unwrap();
+    first.assert_async().await;
+    second.assert_async().await;
+
+    let ids: Vec<&str> = logs.iter().map(|log| log.id.as_str()).collect();
+    assert_eq!(ids, ["a", "b", "c"]);
+}
+
+#[tokio::test]
+async fn test_query_returns_http_errors() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/v1/logs")
+        .with_status(401)
+        .with_body(r#"{"error":"invalid API key"}"#)
+        .create_async()
+        .await;
+
+    let result = query_client(&server.url())
+        .query(&LogQuery::default())
+        .await;
+    assert!(matches!(
+        result,
+        Err(TimberlogsError::Http { status: 401, .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_get_flow_orders_steps() {
+    let mut server = mockito::Server::new_async().await;
+    let step = |index: u32| {
+        let mut step = record(&format!("s{index}"), &format!("step {index}"));
+        step["flowId"] = "import-1a2b".into();
+        step["stepIndex"] = index.into();
+        step
+    };
+    server
+        .mock("GET", "/v1/flows/import-1a2b")
+        .match_header("X-API-Key", "tb_read_key")
+        .with_body(
+            serde_json::json!({
+                "flowId": "import-1a2b",
+                "name": "import",
+                "createdAt": 1_700_000_000_000u64,
+                "steps": [step(2), step(0), step(1)],
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v1/flows/missing")
+        .with_status(404)
+        .create_async()
+        .await;
+
+    let client = query_client(&server.url());
+    let flow = client.get_flow("import-1a2b").await.unwrap().unwrap();
+    assert_eq!(flow.name, "import");
+    let steps: Vec<Option<u32>> = flow.steps.iter().map(|step| step.step_index).collect();
+    assert_eq!(steps, [Some(0), Some(1), Some(2)]);
+
+    assert!(client.get_flow("missing").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_list_flows_sends_filters() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", "/v1/flows")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("name".into(), "nightly export".into()),
+            Matcher::UrlEncoded("from".into(), "100".into()),
+            Matcher::UrlEncoded("to".into(), "200".into()),
+        ]))
+        .with_body(
+            serde_json::json!({
+                "flows": [{ "flowId": "nightly-1", "name": "nightly export", "createdAt": 150, "stepCount": 4 }],
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let page = query_client(&server.url())
+        .list_flows(&FlowQuery {
+            name: Some("nightly export".into()),
+            from: Some(100),
+            to: Some(200),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    mock.assert_async().await;
+    assert_eq!(page.flows[0].step_count, 4);
+    assert_eq!(page.next_cursor, None);
+}
+
+fn sse_event(id: &str, message: &str) -> String {
+    format!("id: {id}\ndata: {}\n\n", record(id, message))
+}
+
+#[tokio::test]
+async fn test_tail_reconnects_and_resumes_after_last_event() {
+    let mut server = mockito::Server::new_async().await;
+    let first = server
+        .mock("GET", "/v1/logs/tail")
+        .match_header("Accept", "text/event-stream")
+        .match_header("Last-Event-ID", Matcher::Missing)
+        .match_query(Matcher::UrlEncoded("flowId".into(), "checkout-1".into()))
+        .with_header("Content-Type", "text/event-stream")
+        .with_body(format!(
+            ": keep-alive\n\n{}{}",
+            sse_event("a", "one"),
+            sse_event("b", "two")
+        ))
+        .create_async()
+        .await;
+    // The first response ends, as if the connection dropped.
+    let resumed = server
+        .mock("GET", "/v1/logs/tail")
+        .match_header("Last-Event-ID", "b")
+        .match_query(Matcher::UrlEncoded("flowId".into(), "checkout-1".into()))
+        .with_header("Content-Type", "text/event-stream")
+        .with_body(sse_event("c", "three"))
+        .create_async()
+        .await;
+
+    let client = query_client(&server.url());
+    let logs: Vec<LogRecord> = client
+        .tail(LogQuery {
+            flow_id: Some("checkout-1".into()),
+            ..Default::default()
+        })
+        .take(3)
+        .try_collect()
+        .await
+        .unwrap();
+    first.assert_async().await;
+    resumed.assert_async().await;
+
+    let messages: Vec<&str> = logs.iter().map(|log| log.message.as_str()).collect();
+    assert_eq!(messages, ["one", "two", "three"]);
+}
+
+#[tokio::test]
+async fn test_tail_resumes_from_record_id_without_event_ids() {
+    let mut server = mockito::Server::new_async().await;
+    let first = server
+        .mock("GET", "/v1/logs/tail")
+        .match_header("Last-Event-ID", Matcher::Missing)
+        .with_header("Content-Type", "text/event-stream")
+        .with_body(format!(
+            "data: {}\n\ndata: {}\n\n",
+            record("a", "one"),
+            record("b", "two")
+        ))
+        .create_async()
+        .await;
+    let resumed = server
+        .mock("GET", "/v1/logs/tail")
+        .match_header("Last-Event-ID", "b")
+        .with_header("Content-Type", "text/event-stream")
+        .with_body(format!("data: {}\n\n", record("c", "three")))
+        .create_async()
+        .await;
+
+    let client = query_client(&server.url());
+    let logs: Vec<LogRecord> = client
+        .tail(LogQuery::default())
+        .take(3)
+        .try_collect()
+        .await
+        .unwrap();
+    first.assert_async().await;
+    resumed.assert_async().await;
+
+    let messages: Vec<&str> = logs.iter().map(|log| log.message.as_str()).collect();
+    assert_eq!(messages, ["one", "two", "three"]);
+}
+
+#[tokio::test]
+async fn test_tail_starts_after_cursor_and_stops_on_rejected_key() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", "/v1/logs/tail")
+        .match_header("Last-Event-ID", "log_9")
+        .with_status(401)
+        .with_body(r#"{"error":"invalid API key"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = query_client(&server.url());
+    let items: Vec<_> = client
+        .tail(LogQuery {
+            cursor: Some("log_9".into()),
+            ..Default::default()
+        })
+        .collect()
+        .await;
+    mock.assert_async().await;
+    assert_eq!(items.len(), 1);
+    assert!(matches!(
+        items[0],
+        Err(TimberlogsError::Http { status: 401, .. })
+    ));
+}
+
+#[test]
+fn test_log_record_from_log() {
+    let mut stored = log("Payment failed");
+    stored.tags = Some(vec!["billing".into()]);
+    let record = LogRecord::from_log(stored, 5000);
+    assert_eq!(record.timestamp, 5000);
+    assert!(!record.id.is_empty());
+    assert_eq!(record.tags, ["billing"]);
+}
+
+#[test]
+fn test_query_client_requires_api_key() {
+    let result = QueryClient::new(QueryConfig::default());
+    assert!(matches!(result, Err(TimberlogsError::Validation(_))));
+}
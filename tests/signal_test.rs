@@ -0,0 +1,95 @@
+#![cfg(all(unix, feature = "shutdown-signal"))]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use timberlogs::{
+    shutdown_all, shutdown_on_signal, shutdown_signal, Environment, InMemoryTransport,
+    ShutdownReport, TimberlogsClient, TimberlogsConfig,
+};
+
+fn client(transport: &Arc<InMemoryTransport>) -> TimberlogsClient {
+    TimberlogsClient::new(TimberlogsConfig {
+        source: "test".into(),
+        environment: Environment::Development,
+        batch_size: Some(100),
+        flush_interval_ms: Some(60000),
+        transport: Some(transport.clone()),
+        ..Default::default()
+    })
+}
+
+fn send_signal(name: &str) {
+    let status = std::process::Command::new("kill")
+        .args([&format!("-{name}"), &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[tokio::test]
+async fn test_shutdown_all_flushes_every_client() {
+    let first = Arc::new(InMemoryTransport::new());
+    let second = Arc::new(InMemoryTransport::new());
+    let a = client(&first);
+    let b = client(&second);
+    a.info("from a", None).await.unwrap();
+    b.info("from b", None).await.unwrap();
+    b.warn("also from b", None).await.unwrap();
+
+    let reports = shutdown_all(&[&a, &b], Duration::from_secs(1)).await;
+
+    assert_eq!(
+        reports[0],
+        ShutdownReport {
+            sent: 1,
+            dropped: 0
+        }
+    );
+    assert_eq!(
+        reports[1],
+        ShutdownReport {
+            sent: 2,
+            dropped: 0
+        }
+    );
+    assert_eq!(second.logs().len(), 2);
+}
+
+// Both signal tests live in one test so they don't race on process-wide handlers.
+#[tokio::test]
+async fn test_signals_trigger_shutdown() {
+    let transport = Arc::new(InMemoryTransport::new());
+    let logger = Arc::new(client(&transport));
+    logger.info("last words", None).await.unwrap();
+
+    let handle = tokio::spawn(shutdown_on_signal(
+        vec![Arc::clone(&logger)],
+        Duration::from_secs(1),
+    ));
+    // Give the handlers a chance to be installed before signalling.
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    send_signal("TERM");
+
+    let reports = tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        reports,
+        [ShutdownReport {
+            sent: 1,
+            dropped: 0
+        }]
+    );
+    assert_eq!(transport.logs()[0].message, "last words");
+    assert!(logger.info("after shutdown", None).await.is_err());
+
+    let waiting = tokio::spawn(shutdown_signal());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    send_signal("INT");
+    tokio::time::timeout(Duration::from_secs(5), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+}
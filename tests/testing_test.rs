@@ -0,0 +1,91 @@
+#![cfg(feature = "testing")]
+
+use std::collections::HashMap;
+
+use timberlogs::testing::CapturedLogs;
+use timberlogs::{LogEntry, LogLevel, TimberlogsClient};
+
+async fn handle_checkout(client: &TimberlogsClient) {
+    let mut flow = client.flow("checkout").await.unwrap();
+    flow.info(
+        "cart loaded",
+        Some(HashMap::from([("items".into(), 3.into())])),
+    )
+    .await
+    .unwrap();
+    flow.log_with_level(
+        LogLevel::Warn,
+        "coupon expired",
+        None,
+        Some(vec!["promo".into()]),
+    )
+    .await
+    .unwrap();
+    client
+        .log(LogEntry {
+            level: LogLevel::Error,
+            message: "payment declined".into(),
+            data: Some(HashMap::from([
+                ("reason".into(), "insufficient_funds".into()),
+                ("amount".into(), 12.5.into()),
+            ])),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_assert_logged_and_filters() {
+    let captured = CapturedLogs::new();
+    let client = TimberlogsClient::new(captured.config());
+    handle_checkout(&client).await;
+
+    captured.assert_logged(LogLevel::Warn, "coupon");
+    captured.assert_not_logged(LogLevel::Warn, "payment");
+
+    let flow_logs = captured.for_flow("checkout-0");
+    assert_eq!(flow_logs.len(), 2);
+    assert_eq!(flow_logs[1].step_index, Some(1));
+    assert_eq!(captured.with_tag("promo")[0].message, "coupon expired");
+    assert_eq!(captured.with_data_key("reason")[0].level, LogLevel::Error);
+    assert_eq!(captured.with_level(LogLevel::Info).len(), 1);
+}
+
+#[tokio::test]
+#[should_panic(expected = "expected a debug log containing \"coupon\"")]
+async fn test_assert_logged_panics_with_captured_logs() {
+    let captured = CapturedLogs::new();
+    let client = TimberlogsClient::new(captured.config());
+    handle_checkout(&client).await;
+
+    captured.assert_logged(LogLevel::Debug, "coupon");
+}
+
+#[tokio::test]
+async fn test_snapshot() {
+    let captured = CapturedLogs::new();
+    let client = TimberlogsClient::new(captured.config());
+    handle_checkout(&client).await;
+
+    assert_eq!(
+        captured.snapshot(),
+        "INFO  cart loaded flow=checkout-0#0 data={\"items\":3}\n\
+         WARN  coupon expired flow=checkout-0#1 tags=[promo]\n\
+         ERROR payment declined data={\"amount\":12.5,\"reason\":\"insufficient_funds\"}"
+    );
+}
+
+#[tokio::test]
+async fn test_unexpected_errors() {
+    let captured = CapturedLogs::new();
+    let client = TimberlogsClient::new(captured.config());
+    handle_checkout(&client).await;
+
+    let strict = captured.clone();
+    let result = std::panic::catch_unwind(move || strict.assert_no_unexpected_errors());
+    assert!(result.is_err());
+
+    captured.expect_error("payment declined");
+    captured.assert_no_unexpected_errors();
+}
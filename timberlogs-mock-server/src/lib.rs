@@ -0,0 +1,660 @@
+mod query;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use timberlogs::{
+    ApiLimits, CreateLogArgs, Environment, FlowDetails, FlowPage, FlowResponse, FlowSummary,
+    IngestResponse, LogLevel, LogPage, LogQuery, LogRecord, RejectedEntry, VerifyResponse,
+};
+use tokio::sync::{oneshot, Notify};
+
+use crate::query::{flow_matches, log_matches, parse_flow_query, parse_log_query};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:0";
+const RAW_FORMATS: [&str; 6] = ["json", "jsonl", "syslog", "text", "csv", "obl"];
+const DEFAULT_QUERY_LIMIT: u32 = 100;
+const MAX_QUERY_LIMIT: u32 = 1000;
+
+#[derive(Default)]
+pub struct MockServerConfig {
+    /// Address to listen on. Defaults to a free port on localhost.
+    pub addr: Option<SocketAddr>,
+    /// When set, requests with a different `X-API-Key` get a 401.
+    pub api_key: Option<String>,
+    /// Reported by `GET /v1/verify`.
+    pub limits: ApiLimits,
+}
+
+/// A canned error response for the next ingest or flow request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fault {
+    pub status: u16,
+    pub retry_after_secs: Option<u64>,
+}
+
+impl Fault {
+    pub fn rate_limited(retry_after_secs: u64) -> Self {
+        Self {
+            status: 429,
+            retry_after_secs: Some(retry_after_secs),
+        }
+    }
+
+    pub fn server_error() -> Self {
+        Self::status(500)
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            retry_after_secs: None,
+        }
+    }
+}
+
+/// A raw-format request to `/v1/logs?format=...`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RawRequest {
+    pub format: String,
+    pub source: Option<String>,
+    pub environment: Option<String>,
+    pub level: Option<String>,
+    pub dataset: Option<String>,
+    pub body: String,
+}
+
+#[derive(Default)]
+struct Received {
+    batches: Vec<Vec<CreateLogArgs>>,
+    // Stored form of every accepted log, in order, for `GET /v1/logs`.
+    records: Vec<LogRecord>,
+    raw: Vec<RawRequest>,
+    flows: Vec<FlowResponse>,
+    // Parallel to `flows`.
+    flow_created_at: Vec<u64>,
+    // Responses by `Idempotency-Key`, and the ids of every stored entry.
+    responses: HashMap<String, IngestResponse>,
+    entry_ids: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Shared {
+    api_key: Option<String>,
+    limits: ApiLimits,
+    received: Mutex<Received>,
+    faults: Mutex<VecDeque<Fault>>,
+    // Wakes tails when logs are stored.
+    stored: Notify,
+}
+
+/// A local stand-in for the Timberlogs ingest API.
+///
+/// Serves `POST /v1/logs` (batches and raw formats), `POST /v1/flows` and
+/// `GET /v1/verify` with the same validation limits as the real API, and
+/// reports `limits` from its config. Invalid entries in an otherwise valid
+/// batch are listed in the response's `rejected` and the
+/// rest are accepted; a batch with nothing valid gets a 400. Batches are
+/// deduplicated like the real API does it: a repeated `Idempotency-Key` gets
+/// the original response, and entries whose `id` was seen before are
+/// accepted but not stored again. Stored logs can be read back with
+/// `GET /v1/logs`, newest first, using the same filters and cursors as the
+/// real API, followed live with `GET /v1/logs/tail`, and flows read with
+/// `GET /v1/flows` and `GET /v1/flows/{id}`. Besides the Rust accessors,
+/// what was received can be read over HTTP from `GET /_mock/logs`,
+/// `/_mock/raw` and `/_mock/flows`, faults queued with `POST /_mock/faults`
+/// and everything reset with `DELETE /_mock`.
+pub struct MockServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    pub async fn start() -> std::io::Result<Self> {
+        Self::with_config(MockServerConfig::default()).await
+    }
+
+    pub async fn with_config(config: MockServerConfig) -> std::io::Result<Self> {
+        let addr = config.addr.unwrap_or_else(|| DEFAULT_ADDR.parse().unwrap());
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+
+        let shared = Arc::new(Shared {
+            api_key: config.api_key,
+            limits: config.limits,
+            ..Default::default()
+        });
+        let app = router(Arc::clone(&shared));
+
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await;
+        });
+
+        Ok(Self {
+            addr,
+            shared,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Base URL to use as the client's `base_url`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn batches(&self) -> Vec<Vec<CreateLogArgs>> {
+        self.shared.received.lock().unwrap().batches.clone()
+    }
+
+    /// Every accepted log, in order, across all batches.
+    pub fn logs(&self) -> Vec<CreateLogArgs> {
+        self.batches().into_iter().flatten().collect()
+    }
+
+    pub fn raw_ingests(&self) -> Vec<RawRequest> {
+        self.shared.received.lock().unwrap().raw.clone()
+    }
+
+    pub fn flows(&self) -> Vec<FlowResponse> {
+        self.shared.received.lock().unwrap().flows.clone()
+    }
+
+    /// Makes the next `count` ingest or flow requests fail with `fault`.
+    pub fn fail_next(&self, count: usize, fault: Fault) {
+        self.shared
+            .faults
+            .lock()
+            .unwrap()
+            .extend(std::iter::repeat_n(fault, count));
+    }
+
+    pub fn clear(&self) {
+        *self.shared.received.lock().unwrap() = Received::default();
+        self.shared.faults.lock().unwrap().clear();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn router(shared: Arc<Shared>) -> Router {
+    Router::new()
+        .route("/v1/logs", post(ingest_logs).get(query_logs))
+        .route("/v1/logs/tail", get(tail_logs))
+        .route("/v1/flows", post(create_flow).get(list_flows))
+        .route("/v1/flows/{id}", get(get_flow))
+        .route("/v1/verify", get(verify))
+        .route("/_mock/logs", get(received_logs))
+        .route("/_mock/raw", get(received_raw))
+        .route("/_mock/flows", get(received_flows))
+        .route("/_mock/faults", post(queue_faults))
+        .route("/_mock", delete(reset))
+        .with_state(shared)
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({ "error": message.into() }))).into_response()
+}
+
+fn unauthorized(shared: &Shared, headers: &HeaderMap) -> Option<Response> {
+    let expected = shared.api_key.as_deref()?;
+    let given = headers.get("x-api-key").and_then(|v| v.to_str().ok());
+    (given != Some(expected)).then(|| error(StatusCode::UNAUTHORIZED, "invalid API key"))
+}
+
+/// Checks the API key and pops the next queued fault, returning the error
+/// response to send instead of handling the request.
+fn reject_request(shared: &Shared, headers: &HeaderMap) -> Option<Response> {
+    if let Some(response) = unauthorized(shared, headers) {
+        return Some(response);
+    }
+
+    let fault = shared.faults.lock().unwrap().pop_front()?;
+    let status = StatusCode::from_u16(fault.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = error(status, format!("injected fault: {}", fault.status));
+    if let Some(secs) = fault.retry_after_secs {
+        response
+            .headers_mut()
+            .insert("retry-after", secs.to_string().parse().unwrap());
+    }
+    Some(response)
+}
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    logs: Vec<CreateLogArgs>,
+}
+
+async fn ingest_logs(
+    State(shared): State<Arc<Shared>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Some(response) = reject_request(&shared, &headers) {
+        return response;
+    }
+    if query.contains_key("format") {
+        return ingest_raw(&shared, query, body);
+    }
+    let key = headers
+        .get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    if let Some(ref key) = key {
+        if let Some(response) = shared.received.lock().unwrap().responses.get(key) {
+            return Json(response.clone()).into_response();
+        }
+    }
+
+    let batch: BatchRequest = match serde_json::from_str(&body) {
+        Ok(batch) => batch,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("invalid batch: {e}")),
+    };
+    if batch.logs.is_empty() {
+        return error(StatusCode::BAD_REQUEST, "logs must not be empty");
+    }
+
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    for (index, log) in batch.logs.into_iter().enumerate() {
+        match log.validate() {
+            Ok(()) => accepted.push(log),
+            Err(e) => rejected.push(RejectedEntry {
+                index,
+                error: e.to_string(),
+            }),
+        }
+    }
+    if accepted.is_empty() {
+        let first = &rejected[0];
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("logs[{}]: {}", first.index, first.error),
+        );
+    }
+
+    let mut response = IngestResponse::accepted(accepted.len());
+    response.rejected = rejected;
+    let mut received = shared.received.lock().unwrap();
+    let Received {
+        batches,
+        records,
+        entry_ids,
+        ..
+    } = &mut *received;
+    accepted.retain(|log| {
+        log.id
+            .as_ref()
+            .is_none_or(|id| entry_ids.insert(id.clone()))
+    });
+    if !accepted.is_empty() {
+        let now = unix_millis();
+        records.extend(
+            accepted
+                .iter()
+                .map(|log| LogRecord::from_log(log.clone(), now)),
+        );
+        batches.push(accepted);
+    }
+    if let Some(key) = key {
+        received.responses.insert(key, response.clone());
+    }
+    drop(received);
+    shared.stored.notify_waiters();
+    Json(response).into_response()
+}
+
+fn ingest_raw(shared: &Shared, mut query: HashMap<String, String>, body: String) -> Response {
+    let format = query.remove("format").unwrap_or_default();
+    if !RAW_FORMATS.contains(&format.as_str()) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("unsupported format: {format}"),
+        );
+    }
+    if let Some(env) = query.get("environment") {
+        if let Err(e) = env.parse::<Environment>() {
+            return error(StatusCode::BAD_REQUEST, e.to_string());
+        }
+    }
+    if let Some(level) = query.get("level") {
+        if let Err(e) = level.parse::<LogLevel>() {
+            return error(StatusCode::BAD_REQUEST, e.to_string());
+        }
+    }
+    if body.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "body must not be empty");
+    }
+
+    let lines = body.lines().filter(|line| !line.trim().is_empty()).count();
+    let count = match format.as_str() {
+        "json" => match serde_json::from_str::<serde_json::Value>(&body) {
+            Ok(serde_json::Value::Array(items)) => items.len(),
+            Ok(_) => 1,
+            Err(e) => return error(StatusCode::BAD_REQUEST, format!("invalid JSON: {e}")),
+        },
+        "csv" => lines.saturating_sub(1),
+        _ => lines,
+    };
+
+    shared.received.lock().unwrap().raw.push(RawRequest {
+        format,
+        source: query.remove("source"),
+        environment: query.remove("environment"),
+        level: query.remove("level"),
+        dataset: query.remove("dataset"),
+        body,
+    });
+    Json(IngestResponse::accepted(count)).into_response()
+}
+
+#[derive(Deserialize)]
+struct FlowRequest {
+    name: String,
+}
+
+async fn create_flow(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Some(response) = reject_request(&shared, &headers) {
+        return response;
+    }
+    let request: FlowRequest = match serde_json::from_str(&body) {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("invalid flow: {e}")),
+    };
+    if request.name.is_empty() {
+        return error(StatusCode::BAD_REQUEST, "name must not be empty");
+    }
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let flow = FlowResponse {
+        flow_id: format!("{}-{}", request.name, &suffix[..8]),
+        name: request.name,
+    };
+    let mut received = shared.received.lock().unwrap();
+    received.flows.push(flow.clone());
+    received.flow_created_at.push(unix_millis());
+    Json(flow).into_response()
+}
+
+// Reads only check the key; faults are for ingest and flow requests.
+async fn verify(State(shared): State<Arc<Shared>>, headers: HeaderMap) -> Response {
+    if let Some(response) = unauthorized(&shared, &headers) {
+        return response;
+    }
+    Json(VerifyResponse {
+        limits: shared.limits.clone(),
+    })
+    .into_response()
+}
+
+// Returns the page size and where to start looking backwards from. The
+// cursor is the position of the last item returned, so pages stay stable
+// while new items arrive.
+fn page_bounds(
+    limit: &Option<u32>,
+    cursor: &Option<String>,
+    len: usize,
+) -> Result<(usize, usize), String> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT);
+    if limit == 0 || limit > MAX_QUERY_LIMIT {
+        return Err(format!("limit must be 1-{MAX_QUERY_LIMIT}, got {limit}"));
+    }
+    let end = match cursor.as_deref().map(str::parse::<usize>) {
+        None => len,
+        Some(Ok(end)) if end <= len => end,
+        Some(_) => return Err("invalid cursor".into()),
+    };
+    Ok((limit as usize, end))
+}
+
+async fn query_logs(
+    State(shared): State<Arc<Shared>>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = unauthorized(&shared, &headers) {
+        return response;
+    }
+    let query = match parse_log_query(&params) {
+        Ok(query) => query,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let received = shared.received.lock().unwrap();
+    let (limit, end) = match page_bounds(&query.limit, &query.cursor, received.records.len()) {
+        Ok(bounds) => bounds,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    let mut matching = received.records[..end]
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, record)| log_matches(&query, record));
+    let mut page = LogPage::default();
+    let mut last = None;
+    for (index, record) in matching.by_ref().take(limit) {
+        page.logs.push(record.clone());
+        last = Some(index);
+    }
+    if matching.next().is_some() {
+        page.next_cursor = last.map(|index| index.to_string());
+    }
+    Json(page).into_response()
+}
+
+// Event ids are record ids; `Last-Event-ID` (or `cursor`) resumes after one.
+async fn tail_logs(
+    State(shared): State<Arc<Shared>>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = unauthorized(&shared, &headers) {
+        return response;
+    }
+    let query = match parse_log_query(&params) {
+        Ok(query) => query,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let last_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| query.cursor.clone());
+
+    let start = {
+        let received = shared.received.lock().unwrap();
+        match last_id {
+            None => received.records.len(),
+            Some(id) => match received.records.iter().position(|record| record.id == id) {
+                Some(index) => index + 1,
+                None => return error(StatusCode::BAD_REQUEST, format!("unknown cursor: {id}")),
+            },
+        }
+    };
+    Sse::new(tail_events(shared, query, start))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn tail_events(
+    shared: Arc<Shared>,
+    query: LogQuery,
+    start: usize,
+) -> impl Stream<Item = Result<Event, axum::Error>> {
+    stream::unfold(
+        (shared, query, start),
+        |(shared, query, mut next)| async move {
+            loop {
+                // Registered before looking so a store in between isn't missed.
+                let stored = shared.stored.notified();
+                let found = {
+                    let received = shared.received.lock().unwrap();
+                    next = next.min(received.records.len());
+                    let found = received.records[next..]
+                        .iter()
+                        .position(|record| log_matches(&query, record))
+                        .map(|offset| (next + offset, received.records[next + offset].clone()));
+                    if found.is_none() {
+                        next = received.records.len();
+                    }
+                    found
+                };
+                if let Some((index, record)) = found {
+                    drop(stored);
+                    let event = Event::default().id(record.id.clone()).json_data(&record);
+                    return Some((event, (shared, query, index + 1)));
+                }
+                stored.await;
+            }
+        },
+    )
+}
+
+fn flow_summary(received: &Received, index: usize) -> FlowSummary {
+    let flow = &received.flows[index];
+    let steps = received
+        .records
+        .iter()
+        .filter(|record| record.flow_id.as_deref() == Some(flow.flow_id.as_str()))
+        .count();
+    FlowSummary {
+        flow_id: flow.flow_id.clone(),
+        name: flow.name.clone(),
+        created_at: received.flow_created_at[index],
+        step_count: steps as u32,
+    }
+}
+
+async fn list_flows(
+    State(shared): State<Arc<Shared>>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = unauthorized(&shared, &headers) {
+        return response;
+    }
+    let query = match parse_flow_query(&params) {
+        Ok(query) => query,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let received = shared.received.lock().unwrap();
+    let (limit, end) = match page_bounds(&query.limit, &query.cursor, received.flows.len()) {
+        Ok(bounds) => bounds,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    let mut matching = (0..end)
+        .rev()
+        .map(|index| (index, flow_summary(&received, index)))
+        .filter(|(_, flow)| flow_matches(&query, flow));
+    let mut page = FlowPage::default();
+    let mut last = None;
+    for (index, flow) in matching.by_ref().take(limit) {
+        page.flows.push(flow);
+        last = Some(index);
+    }
+    if matching.next().is_some() {
+        page.next_cursor = last.map(|index| index.to_string());
+    }
+    Json(page).into_response()
+}
+
+async fn get_flow(
+    State(shared): State<Arc<Shared>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(response) = unauthorized(&shared, &headers) {
+        return response;
+    }
+    let received = shared.received.lock().unwrap();
+    let Some(index) = received.flows.iter().position(|flow| flow.flow_id == id) else {
+        return error(StatusCode::NOT_FOUND, format!("flow not found: {id}"));
+    };
+    let mut steps: Vec<LogRecord> = received
+        .records
+        .iter()
+        .filter(|record| record.flow_id.as_deref() == Some(id.as_str()))
+        .cloned()
+        .collect();
+    steps.sort_by_key(|step| step.step_index);
+    Json(FlowDetails {
+        flow_id: id,
+        name: received.flows[index].name.clone(),
+        created_at: received.flow_created_at[index],
+        steps,
+    })
+    .into_response()
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+async fn received_logs(State(shared): State<Arc<Shared>>) -> Json<Vec<CreateLogArgs>> {
+    let received = shared.received.lock().unwrap();
+    Json(received.batches.iter().flatten().cloned().collect())
+}
+
+async fn received_raw(State(shared): State<Arc<Shared>>) -> Json<Vec<RawRequest>> {
+    Json(shared.received.lock().unwrap().raw.clone())
+}
+
+async fn received_flows(State(shared): State<Arc<Shared>>) -> Json<Vec<FlowResponse>> {
+    Json(shared.received.lock().unwrap().flows.clone())
+}
+
+#[derive(Deserialize)]
+struct FaultRequest {
+    #[serde(flatten)]
+    fault: Fault,
+    count: Option<usize>,
+}
+
+async fn queue_faults(
+    State(shared): State<Arc<Shared>>,
+    Json(request): Json<FaultRequest>,
+) -> StatusCode {
+    shared.faults.lock().unwrap().extend(std::iter::repeat_n(
+        request.fault,
+        request.count.unwrap_or(1),
+    ));
+    StatusCode::NO_CONTENT
+}
+
+async fn reset(State(shared): State<Arc<Shared>>) -> StatusCode {
+    *shared.received.lock().unwrap() = Received::default();
+    shared.faults.lock().unwrap().clear();
+    StatusCode::NO_CONTENT
+}
@@ -0,0 +1,53 @@
+use std::net::SocketAddr;
+use std::process::ExitCode;
+
+use timberlogs_mock_server::{MockServer, MockServerConfig};
+
+const USAGE: &str = "usage: timberlogs-mock-server [--addr HOST:PORT] [--api-key KEY]";
+
+fn parse_args() -> Result<MockServerConfig, String> {
+    let mut config = MockServerConfig {
+        addr: Some(SocketAddr::from(([127, 0, 0, 1], 8787))),
+        ..Default::default()
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--addr" => {
+                let addr = value()?;
+                config.addr = Some(
+                    addr.parse()
+                        .map_err(|e| format!("invalid --addr {addr}: {e}"))?,
+                );
+            }
+            "--api-key" => config.api_key = Some(value()?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown argument: {arg}\n{USAGE}")),
+        }
+    }
+    Ok(config)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let config = match parse_args() {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let server = match MockServer::with_config(config).await {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("failed to start: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("timberlogs mock server listening on {}", server.url());
+
+    let _ = tokio::signal::ctrl_c().await;
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,109 @@
+//! Server side of the query API: parsing the parameters `LogQuery` and
+//! `FlowQuery` send, and applying them to stored records.
+
+use std::collections::HashMap;
+
+use timberlogs::{Environment, FlowQuery, FlowSummary, LogQuery, LogRecord, TimberlogsError};
+
+/// Parses parameters written by `LogQuery::to_params`. Unknown parameters are
+/// ignored.
+pub(crate) fn parse_log_query(
+    params: &HashMap<String, String>,
+) -> Result<LogQuery, TimberlogsError> {
+    let string = |name: &str| params.get(name).cloned();
+    let list = |name: &str| -> Vec<String> {
+        params.get(name).map_or_else(Vec::new, |v| {
+            v.split(',')
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+    };
+
+    Ok(LogQuery {
+        levels: list("level")
+            .iter()
+            .map(|level| level.parse())
+            .collect::<Result<_, _>>()?,
+        source: string("source"),
+        environment: params
+            .get("environment")
+            .map(Environment::custom)
+            .transpose()?,
+        dataset: string("dataset"),
+        user_id: string("userId"),
+        session_id: string("sessionId"),
+        flow_id: string("flowId"),
+        tags: list("tags"),
+        from: parse_param(params, "from")?,
+        to: parse_param(params, "to")?,
+        search: string("q"),
+        limit: parse_param(params, "limit")?,
+        cursor: string("cursor"),
+    })
+}
+
+/// Whether `record` passes every filter in `query`. Ignores `limit` and
+/// `cursor`.
+pub(crate) fn log_matches(query: &LogQuery, record: &LogRecord) -> bool {
+    fn eq(filter: &Option<String>, value: Option<&str>) -> bool {
+        filter.as_deref().is_none_or(|filter| value == Some(filter))
+    }
+
+    (query.levels.is_empty() || query.levels.contains(&record.level))
+        && eq(&query.source, Some(&record.source))
+        && query
+            .environment
+            .as_ref()
+            .is_none_or(|env| *env == record.environment)
+        && eq(&query.dataset, record.dataset.as_deref())
+        && eq(&query.user_id, record.user_id.as_deref())
+        && eq(&query.session_id, record.session_id.as_deref())
+        && eq(&query.flow_id, record.flow_id.as_deref())
+        && query.tags.iter().all(|tag| record.tags.contains(tag))
+        && query.from.is_none_or(|from| record.timestamp >= from)
+        && query.to.is_none_or(|to| record.timestamp < to)
+        && query.search.as_deref().is_none_or(|search| {
+            let search = search.to_lowercase();
+            [
+                Some(record.message.as_str()),
+                record.error_name.as_deref(),
+                record.error_stack.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|text| text.to_lowercase().contains(&search))
+        })
+}
+
+/// Parses parameters written by `FlowQuery::to_params`.
+pub(crate) fn parse_flow_query(
+    params: &HashMap<String, String>,
+) -> Result<FlowQuery, TimberlogsError> {
+    Ok(FlowQuery {
+        name: params.get("name").cloned(),
+        from: parse_param(params, "from")?,
+        to: parse_param(params, "to")?,
+        limit: parse_param(params, "limit")?,
+        cursor: params.get("cursor").cloned(),
+    })
+}
+
+pub(crate) fn flow_matches(query: &FlowQuery, flow: &FlowSummary) -> bool {
+    query.name.as_ref().is_none_or(|name| *name == flow.name)
+        && query.from.is_none_or(|from| flow.created_at >= from)
+        && query.to.is_none_or(|to| flow.created_at < to)
+}
+
+fn parse_param<T: std::str::FromStr>(
+    params: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<T>, TimberlogsError> {
+    params
+        .get(name)
+        .map(|v| {
+            v.parse()
+                .map_err(|_| TimberlogsError::Validation(format!("invalid {name}: {v}")))
+        })
+        .transpose()
+}
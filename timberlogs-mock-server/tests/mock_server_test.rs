@@ -0,0 +1,518 @@
+use std::sync::Arc;
+
+use futures_util::{StreamExt, TryStreamExt};
+use timberlogs::{
+    ApiLimits, Environment, FileAgent, FileAgentConfig, FlowQuery, IngestRawOptions, LogEntry,
+    LogLevel, LogQuery, LogRecord, QueryClient, QueryConfig, RawFormat, RetryConfig,
+    TimberlogsClient, TimberlogsConfig, TimberlogsError, WatchedFile,
+};
+use timberlogs_mock_server::{Fault, MockServer, MockServerConfig};
+
+fn client_config(server: &MockServer) -> TimberlogsConfig {
+    TimberlogsConfig {
+        source: "test".into(),
+        environment: Environment::Staging,
+        api_key: "tb_key".into(),
+        batch_size: Some(10),
+        flush_interval_ms: Some(60000),
+        base_url: Some(server.url()),
+        retry: Some(RetryConfig {
+            max_retries: 2,
+            initial_delay_ms: 10,
+            max_delay_ms: 10,
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_receives_batches_and_flows() {
+    let server = MockServer::start().await.unwrap();
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(1),
+        ..client_config(&server)
+    });
+
+    client.warn("low disk", None).await.unwrap();
+    let mut flow = client.flow("import").await.unwrap();
+    flow.info("started", None).await.unwrap();
+    let flow_id = flow.id.clone();
+    drop(flow);
+    client.disconnect().await.unwrap();
+
+    let logs = server.logs();
+    let warn = logs.iter().find(|log| log.level == LogLevel::Warn).unwrap();
+    assert_eq!(warn.message, "low disk");
+    assert_eq!(warn.environment, Environment::Staging);
+    let step = logs.iter().find(|log| log.flow_id.is_some()).unwrap();
+    assert_eq!(step.flow_id.as_deref(), Some(flow_id.as_str()));
+    assert_eq!(step.step_index, Some(0));
+    assert!(flow_id.starts_with("import-"));
+    assert_eq!(server.flows()[0].name, "import");
+}
+
+#[tokio::test]
+async fn test_rejects_invalid_logs_and_api_key() {
+    let server = MockServer::with_config(MockServerConfig {
+        api_key: Some("tb_key".into()),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let http = reqwest::Client::new();
+    let url = format!("{}/v1/logs", server.url());
+
+    let response = http
+        .post(&url)
+        .header("X-API-Key", "tb_key")
+        .json(&serde_json::json!({
+            "logs": [{ "level": "info", "message": "", "source": "t", "environment": "development" }]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let message = body["error"].as_str().unwrap();
+    assert!(message.starts_with("logs[0]:"), "{message}");
+    assert!(message.contains("message must not be empty"), "{message}");
+
+    let mut client = TimberlogsClient::new(TimberlogsConfig {
+        api_key: "wrong".into(),
+        ..client_config(&server)
+    });
+    client.info("hello", None).await.unwrap();
+    let result = client.disconnect().await;
+    assert!(matches!(
+        result,
+        Err(TimberlogsError::Http { status: 401, .. })
+    ));
+    assert!(server.logs().is_empty());
+}
+
+#[tokio::test]
+async fn test_verify_checks_key_and_reports_limits() {
+    let limits = ApiLimits {
+        max_batch_size: Some(100),
+        ..Default::default()
+    };
+    let server = MockServer::with_config(MockServerConfig {
+        api_key: Some("tb_key".into()),
+        limits: limits.clone(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let client = TimberlogsClient::new(client_config(&server));
+    let report = client.verify().await.unwrap();
+    assert!(report.authenticated);
+    assert_eq!(report.limits, limits);
+    assert!(report.clock_skew_ms.unwrap().abs() < 2000);
+
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        api_key: "wrong".into(),
+        ..client_config(&server)
+    });
+    assert!(!client.verify().await.unwrap().authenticated);
+}
+
+#[tokio::test]
+async fn test_query_filters_and_pages_newest_first() {
+    let server = MockServer::start().await.unwrap();
+    let client = TimberlogsClient::new(client_config(&server));
+    for i in 0..5 {
+        let level = if i % 2 == 0 {
+            LogLevel::Error
+        } else {
+            LogLevel::Info
+        };
+        client
+            .log(LogEntry {
+                level,
+                message: format!("request {i} done"),
+                tags: Some(vec![format!("n{i}")]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+    client.flush().await.unwrap();
+
+    let reader = QueryClient::new(QueryConfig {
+        api_key: "tb_key".into(),
+        base_url: Some(server.url()),
+        ..Default::default()
+    })
+    .unwrap();
+    let errors = LogQuery {
+        levels: vec![LogLevel::Error],
+        limit: Some(2),
+        ..Default::default()
+    };
+    let page = reader.query(&errors).await.unwrap();
+    let messages: Vec<&str> = page.logs.iter().map(|log| log.message.as_str()).collect();
+    assert_eq!(messages, ["request 4 done", "request 2 done"]);
+    assert!(page.next_cursor.is_some());
+
+    let all: Vec<LogRecord> = reader.query_all(errors).try_collect().await.unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[2].message, "request 0 done");
+    assert_eq!(all[2].source, "test");
+    assert!(all[2].timestamp > 0);
+
+    let tagged = reader
+        .query(&LogQuery {
+            tags: vec!["n3".into()],
+            search: Some("REQUEST".into()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(tagged.logs.len(), 1);
+    assert_eq!(tagged.logs[0].level, LogLevel::Info);
+    assert_eq!(tagged.next_cursor, None);
+
+    let too_many = reader
+        .query(&LogQuery {
+            limit: Some(5000),
+            ..Default::default()
+        })
+        .await;
+    assert!(matches!(
+        too_many,
+        Err(TimberlogsError::Http { status: 400, .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_query_filters_and_rejects_bad_params() {
+    let server = MockServer::start().await.unwrap();
+    let client = TimberlogsClient::new(client_config(&server));
+    client
+        .log(LogEntry {
+            level: LogLevel::Error,
+            message: "Payment failed".into(),
+            error_stack: Some("at charge_card".into()),
+            session_id: Some("sess_1".into()),
+            tags: Some(vec!["billing".into(), "eu".into()]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    client.info("other", None).await.unwrap();
+    client.flush().await.unwrap();
+
+    let reader = QueryClient::new(QueryConfig {
+        api_key: "tb_key".into(),
+        base_url: Some(server.url()),
+        ..Default::default()
+    })
+    .unwrap();
+    let count = |query: LogQuery| {
+        let reader = &reader;
+        async move { reader.query(&query).await.unwrap().logs.len() }
+    };
+    assert_eq!(count(LogQuery::default()).await, 2);
+    assert_eq!(
+        count(LogQuery {
+            search: Some("CHARGE_CARD".into()),
+            tags: vec!["eu".into()],
+            environment: Some(Environment::Staging),
+            session_id: Some("sess_1".into()),
+            ..Default::default()
+        })
+        .await,
+        1
+    );
+    assert_eq!(
+        count(LogQuery {
+            tags: vec!["billing".into(), "us".into()],
+            ..Default::default()
+        })
+        .await,
+        0
+    );
+    assert_eq!(
+        count(LogQuery {
+            environment: Some(Environment::Production),
+            ..Default::default()
+        })
+        .await,
+        0
+    );
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/v1/logs?level=loud", server.url()))
+        .header("X-API-Key", "tb_key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    assert!(response.text().await.unwrap().contains("unknown log level"));
+}
+
+#[tokio::test]
+async fn test_tail_follows_new_logs_and_resumes_from_cursor() {
+    let server = MockServer::start().await.unwrap();
+    let client = TimberlogsClient::new(TimberlogsConfig {
+        batch_size: Some(1),
+        ..client_config(&server)
+    });
+    client.info("before the tail", None).await.unwrap();
+
+    let reader = QueryClient::new(QueryConfig {
+        api_key: "tb_key".into(),
+        base_url: Some(server.url()),
+        ..Default::default()
+    })
+    .unwrap();
+    let session = LogQuery {
+        session_id: Some("sess_1".into()),
+        ..Default::default()
+    };
+    let mut tail = Box::pin(reader.tail(session.clone()));
+    let writer = tokio::spawn(async move {
+        // Give the tail time to connect, since it only sees new logs.
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        for (message, session_id) in [("a", "sess_1"), ("other", "sess_2"), ("b", "sess_1")] {
+            client
+                .log(LogEntry {
+                    message: message.into(),
+                    session_id: Some(session_id.into()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+        client
+    });
+
+    let first = tail.next().await.unwrap().unwrap();
+    let second = tail.next().await.unwrap().unwrap();
+    assert_eq!(
+        (first.message.as_str(), second.message.as_str()),
+        ("a", "b")
+    );
+    drop(tail);
+    writer.await.unwrap();
+
+    let resumed = std::pin::pin!(reader.tail(LogQuery {
+        cursor: Some(first.id.clone()),
+        ..session
+    }))
+    .next()
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(resumed.id, second.id);
+}
+
+#[tokio::test]
+async fn test_reads_flows_back_with_ordered_steps() {
+    let server = MockServer::start().await.unwrap();
+    let client = TimberlogsClient::new(client_config(&server));
+    let mut import = client.flow("import").await.unwrap();
+    import.info("download", None).await.unwrap();
+    import.info("parse", None).await.unwrap();
+    import.info("store", None).await.unwrap();
+    let import_id = import.id.clone();
+    drop(import);
+    client.flow("export").await.unwrap();
+    client.flush().await.unwrap();
+
+    let reader = QueryClient::new(QueryConfig {
+        api_key: "tb_key".into(),
+        base_url: Some(server.url()),
+        ..Default::default()
+    })
+    .unwrap();
+    let flow = reader.get_flow(&import_id).await.unwrap().unwrap();
+    assert_eq!(flow.name, "import");
+    let steps: Vec<&str> = flow
+        .steps
+        .iter()
+        .map(|step| step.message.as_str())
+        .collect();
+    assert_eq!(steps, ["download", "parse", "store"]);
+    assert!(reader.get_flow("nope").await.unwrap().is_none());
+
+    let all = reader.list_flows(&FlowQuery::default()).await.unwrap();
+    let names: Vec<&str> = all.flows.iter().map(|flow| flow.name.as_str()).collect();
+    assert_eq!(names, ["export", "import"]);
+    assert_eq!(all.flows[1].step_count, 3);
+
+    let imports = reader
+        .list_flows(&FlowQuery {
+            name: Some("import".into()),
+            from: Some(flow.created_at),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(imports.flows.len(), 1);
+    assert_eq!(imports.flows[0].flow_id, import_id);
+}
+
+#[tokio::test]
+async fn test_partial_batch_lists_rejected_entries() {
+    let server = MockServer::start().await.unwrap();
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(format!("{}/v1/logs", server.url()))
+        .json(&serde_json::json!({
+            "logs": [
+                { "level": "info", "message": "ok", "source": "t", "environment": "development" },
+                { "level": "info", "message": "", "source": "t", "environment": "development" }
+            ]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(response["count"], 1);
+    assert_eq!(response["rejected"][0]["index"], 1);
+    assert_eq!(server.logs().len(), 1);
+    assert_eq!(server.logs()[0].message, "ok");
+}
+
+#[tokio::test]
+async fn test_deduplicates_by_idempotency_key_and_entry_id() {
+    let server = MockServer::start().await.unwrap();
+    let http = reqwest::Client::new();
+    let url = format!("{}/v1/logs", server.url());
+    let log = |id: &str| {
+        serde_json::json!({
+            "id": id, "level": "info", "message": "m", "source": "t", "environment": "development"
+        })
+    };
+
+    for _ in 0..2 {
+        let response = http
+            .post(&url)
+            .header("Idempotency-Key", "batch-1")
+            .json(&serde_json::json!({ "logs": [log("a"), log("b")] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    http.post(&url)
+        .header("Idempotency-Key", "batch-2")
+        .json(&serde_json::json!({ "logs": [log("b"), log("c")] }))
+        .send()
+        .await
+        .unwrap();
+
+    let ids: Vec<String> = server.logs().into_iter().filter_map(|log| log.id).collect();
+    assert_eq!(ids, ["a", "b", "c"]);
+}
+
+#[tokio::test]
+async fn test_injected_faults_are_retried() {
+    let server = MockServer::start().await.unwrap();
+    server.fail_next(1, Fault::rate_limited(1));
+    server.fail_next(1, Fault::server_error());
+
+    let mut client = TimberlogsClient::new(client_config(&server));
+    client
+        .log(LogEntry {
+            message: "eventually delivered".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    client.disconnect().await.unwrap();
+
+    assert_eq!(server.logs().len(), 1);
+}
+
+#[tokio::test]
+async fn test_raw_ingest_and_http_queries() {
+    let server = MockServer::start().await.unwrap();
+    let client = TimberlogsClient::new(client_config(&server));
+
+    client
+        .ingest_raw(
+            "name,level\na,info\nb,warn",
+            RawFormat::Csv,
+            Some(IngestRawOptions {
+                level: Some(LogLevel::Warn),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+    let raw = server.raw_ingests();
+    assert_eq!(raw[0].format, "csv");
+    assert_eq!(raw[0].level.as_deref(), Some("warn"));
+
+    let http = reqwest::Client::new();
+    let received: serde_json::Value = http
+        .get(format!("{}/_mock/raw", server.url()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(received[0]["body"], "name,level\na,info\nb,warn");
+
+    http.post(format!("{}/_mock/faults", server.url()))
+        .json(&serde_json::json!({ "status": 503, "count": 1 }))
+        .send()
+        .await
+        .unwrap();
+    let response = http
+        .post(format!("{}/v1/flows", server.url()))
+        .json(&serde_json::json!({ "name": "x" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+
+    http.delete(format!("{}/_mock", server.url()))
+        .send()
+        .await
+        .unwrap();
+    assert!(server.raw_ingests().is_empty());
+}
+
+// The server drops entries whose id it has seen, so lines from a rotated or
+// truncated file must not reuse the ids of the lines they replaced.
+#[cfg(unix)]
+#[tokio::test]
+async fn test_file_agent_lines_survive_rotation_and_truncation() {
+    let server = MockServer::start().await.unwrap();
+    let dir = std::env::temp_dir().join(format!("timberlogs-mock-agent-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.log");
+    std::fs::write(&path, "one\n").unwrap();
+
+    let client = Arc::new(TimberlogsClient::new(client_config(&server)));
+    let mut agent = FileAgent::new(
+        client,
+        FileAgentConfig {
+            files: vec![WatchedFile::new(&path, RawFormat::Text)],
+            checkpoint_path: dir.join("checkpoint.json"),
+            read_existing: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    agent.poll().await.unwrap();
+
+    // copytruncate, rewritten past the old offset before the next poll.
+    std::fs::write(&path, "two\nthree\n").unwrap();
+    agent.poll().await.unwrap();
+    // Rotated; the new file's first line sits at the same offset as "two".
+    std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+    std::fs::write(&path, "four\n").unwrap();
+    agent.poll().await.unwrap();
+
+    let messages: Vec<String> = server.logs().into_iter().map(|log| log.message).collect();
+    assert_eq!(messages, ["one", "two", "three", "four"]);
+}